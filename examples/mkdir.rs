@@ -1,6 +1,10 @@
-fn main(){
+fn main() {
     let mut dt = dtree::DTree::new();
     dt.mkdir("Marks Test Directory").unwrap();
-    dt.children[0].subdir.mkdir("Marks Other Directory").unwrap();
+    dt.children[0]
+        .subdir_mut()
+        .unwrap()
+        .mkdir("Marks Other Directory")
+        .unwrap();
     println!("{:?}", &dt.children);
-}
\ No newline at end of file
+}
@@ -0,0 +1,133 @@
+//! Clocks: where entry timestamps come from.
+//!
+//! [`OsState`](crate::OsState) stamps entries using its clock, so giving it a [`FakeClock`]
+//! makes timestamps reproducible. [`DTree`] methods, which have no operating system state to
+//! consult, stamp entries using the clock set for the thread by [`DTree::with_clock`], which
+//! is the [`SystemClock`] unless set otherwise.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use crate::DTree;
+
+thread_local! {
+    /// The clock [`DTree`] methods stamp entries with on this thread.
+    static TREE_CLOCK: RefCell<Rc<dyn Clock>> = RefCell::new(Rc::new(SystemClock));
+}
+
+/// The current time by the clock [`DTree`] methods use on this thread.
+pub(crate) fn tree_now() -> SystemTime {
+    TREE_CLOCK.with(|c| Rc::clone(&c.borrow())).now()
+}
+
+/// Puts back the clock [`DTree::with_clock`] replaced, even if its function panics.
+struct Restore(Option<Rc<dyn Clock>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(clock) = self.0.take() {
+            TREE_CLOCK.with(|c| *c.borrow_mut() = clock);
+        }
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Call `f`, with [`DTree`] methods on this thread stamping entries using `clock` instead
+    /// of the clock they used before, which is put back when `f` returns. This covers every
+    /// method that stamps times, including building trees with [`DTree::from_paths`] and the
+    /// parsers, and making entries with [`DEnt::new`](crate::DEnt::new) and its relatives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, FakeClock};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let clock = FakeClock::new(UNIX_EPOCH);
+    /// let dt = DTree::with_clock(clock.clone(), || {
+    ///     let mut dt = DTree::new();
+    ///     dt.create("f").unwrap();
+    ///     clock.advance(Duration::from_secs(5));
+    ///     dt.write("f", b"text").unwrap();
+    ///     dt
+    /// });
+    /// assert_eq!(dt.stat("f").unwrap().meta.mtime, UNIX_EPOCH + Duration::from_secs(5));
+    /// assert_eq!(dt.stat("f").unwrap().meta.atime, UNIX_EPOCH);
+    /// ```
+    pub fn with_clock<C, R, F>(clock: C, f: F) -> R
+    where
+        C: Clock + 'static,
+        F: FnOnce() -> R,
+    {
+        let clock: Rc<dyn Clock> = Rc::new(clock);
+        let _restore = Restore(Some(TREE_CLOCK.with(|c| c.replace(clock))));
+        f()
+    }
+}
+
+/// A source of the current time.
+pub trait Clock: Debug {
+    /// The current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The real time of day, as given by [`SystemTime::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test can keep one
+/// clone to advance while an [`OsState`](crate::OsState) reads another.
+///
+/// # Examples
+///
+/// ```
+/// # use dtree::{Clock, FakeClock};
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let clock = FakeClock::new(UNIX_EPOCH);
+/// let other = clock.clone();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(other.now(), UNIX_EPOCH + Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    now: Rc<Cell<SystemTime>>,
+}
+
+impl FakeClock {
+    /// Create a clock stopped at `start`.
+    pub fn new(start: SystemTime) -> Self {
+        FakeClock {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+
+    /// Set the clock to `now`.
+    pub fn set(&self, now: SystemTime) {
+        self.now.set(now);
+    }
+}
+
+impl Default for FakeClock {
+    /// A clock stopped at the Unix epoch.
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+}
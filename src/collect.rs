@@ -0,0 +1,154 @@
+//! Building a tree from leaf paths, the inverse of [`DTree::paths`].
+
+use std::iter::FromIterator;
+use std::time::SystemTime;
+
+use thiserror::Error;
+
+use crate::clock::tree_now;
+use crate::{DPath, DTree, DirError, Node, NodeKind, Result};
+
+/// An error in one of the paths a tree was being built from.
+#[derive(Error, Debug)]
+#[error("path {index} ({path:?}): {error}")]
+pub struct PathListError<'a> {
+    /// The position of the path in the input, counted from 0.
+    pub index: usize,
+    /// The path itself.
+    pub path: &'a str,
+    /// What was wrong with it.
+    pub error: DirError<'a>,
+}
+
+impl<'a> DTree<'a> {
+    /// Add the leaf given by `path` to this directory, as [`DTree::paths`] describes it: a
+    /// path ending in `/` is a directory, and any other path is an empty regular file. Missing
+    /// directories along the path are made. It is not an error for the leaf to exist already
+    /// as the same kind of entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, DirError};
+    /// let mut dt = DTree::new();
+    /// dt.insert_path("/a/b/").unwrap();
+    /// dt.insert_path("/a/f").unwrap();
+    /// dt.insert_path("/a/f").unwrap();
+    /// assert!(dt.insert_path("/a/f/").is_err());
+    /// assert!(dt.insert_path("/a/../g").is_err());
+    /// assert_eq!(&dt.paths(), &["/a/b/", "/a/f"]);
+    /// dt.symlink("a", "l").unwrap();
+    /// assert!(matches!(dt.insert_path("/l"), Err(DirError::DirExists("/l"))));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidName` if a component of `path` is `.` or `..`.
+    /// * `DirError::NotADirectory` if a component of `path` before the last is a file.
+    /// * `DirError::DirExists` if `path` is a directory that exists as a file, or if `path`
+    ///   exists as a symbolic link.
+    /// * `DirError::IsADirectory` if `path` is a file that exists as a directory.
+    pub fn insert_path(&mut self, path: &'a str) -> Result<'a, ()> {
+        self.insert_leaf(path, tree_now())
+    }
+
+    /// Add the leaf given by `path`, as for [`DTree::insert_path`], made at `now`.
+    fn insert_leaf(&mut self, path: &'a str, now: SystemTime) -> Result<'a, ()> {
+        let parsed = DPath::parse(path)?;
+        let components = parsed.components();
+        if let Some(&c) = components.iter().find(|&&c| c == "." || c == "..") {
+            return Err(DirError::InvalidName(c));
+        }
+        let (&name, parent) = match components.split_last() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        self.make_all(Vec::new(), parent, false, now)?;
+        let existing = self.in_dir(parent, |dt| dt.child(name).map(|d| d.kind()))?;
+        match (existing, path.ends_with('/')) {
+            (None, true) => self.make_all(parent.to_vec(), &[name], false, now),
+            (None, false) => self.add_entry(path, components, Node::File(Vec::new()), now),
+            (Some(NodeKind::Dir), true) | (Some(NodeKind::File), false) => Ok(()),
+            (Some(NodeKind::Symlink), _) | (Some(_), true) => Err(DirError::DirExists(path)),
+            (Some(_), false) => Err(DirError::IsADirectory(path)),
+        }
+    }
+
+    /// Build a tree from leaf paths, as described for [`DTree::insert_path`]. Component names
+    /// are borrowed from the paths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let dt = DTree::from_paths(&["/a/b/", "/a/f", "/c/"]).unwrap();
+    /// assert_eq!(&dt.paths(), &["/a/b/", "/a/f", "/c/"]);
+    ///
+    /// let err = DTree::from_paths(&["/a/", "/a/b//c", "/a/./d/"]).unwrap_err();
+    /// assert_eq!((err.index, err.path), (2, "/a/./d/"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * A [`PathListError`] giving the first path that could not be added, and why, as for
+    ///   [`DTree::insert_path`].
+    pub fn from_paths<I, S>(paths: I) -> std::result::Result<Self, PathListError<'a>>
+    where
+        I: IntoIterator<Item = &'a S>,
+        S: AsRef<str> + ?Sized + 'a,
+    {
+        let now = tree_now();
+        let mut dt = DTree::new();
+        for (index, path) in paths.into_iter().enumerate() {
+            let path = path.as_ref();
+            dt.insert_leaf(path, now)
+                .map_err(|error| PathListError { index, path, error })?;
+        }
+        Ok(dt)
+    }
+}
+
+impl<'a, S: AsRef<str> + ?Sized + 'a> Extend<&'a S> for DTree<'a> {
+    /// Add each leaf path, as with [`DTree::insert_path`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a path cannot be added: see [`DTree::from_paths`] for a constructor that
+    /// reports the error instead.
+    fn extend<I: IntoIterator<Item = &'a S>>(&mut self, paths: I) {
+        let now = tree_now();
+        for path in paths {
+            let path = path.as_ref();
+            if let Err(e) = self.insert_leaf(path, now) {
+                panic!("cannot add path {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+impl<'a, S: AsRef<str> + ?Sized + 'a> FromIterator<&'a S> for DTree<'a> {
+    /// Build a tree from leaf paths, as with [`DTree::from_paths`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.mkdir("c").unwrap();
+    /// let paths = dt.paths();
+    /// let copy: DTree = paths.iter().collect();
+    /// assert_eq!(copy.paths(), paths);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a path cannot be added.
+    fn from_iter<I: IntoIterator<Item = &'a S>>(paths: I) -> Self {
+        let mut dt = DTree::new();
+        dt.extend(paths);
+        dt
+    }
+}
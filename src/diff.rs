@@ -0,0 +1,232 @@
+//! Structural differences between two trees.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{DEnt, DTree, Node, NodeKind, Stat};
+
+/// One way in which an entry present in both trees differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Aspect {
+    /// The entry is a different kind of node. No other aspects are compared.
+    Kind,
+    /// The permission bits differ.
+    Mode,
+    /// The owning user or group differs.
+    Owner,
+    /// The modification time differs. Not compared for directories, whose modification
+    /// times follow their entries, which are compared on their own.
+    Mtime,
+    /// The contents of a file, or the target of a symbolic link, differ.
+    Contents,
+}
+
+impl Aspect {
+    /// The name of this aspect as rendered.
+    fn name(self) -> &'static str {
+        match self {
+            Aspect::Kind => "kind",
+            Aspect::Mode => "mode",
+            Aspect::Owner => "owner",
+            Aspect::Mtime => "mtime",
+            Aspect::Contents => "contents",
+        }
+    }
+}
+
+/// How an entry changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChangeKind {
+    /// The entry is only in the new tree.
+    Added,
+    /// The entry is only in the old tree.
+    Removed,
+    /// The entry is in both trees, differing in the given aspects.
+    Modified(Vec<Aspect>),
+}
+
+/// A changed entry, as found by [`DTree::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change<'a> {
+    /// The component names leading to the entry from the top of the trees.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub path: Vec<&'a str>,
+    /// How the entry changed.
+    pub kind: ChangeKind,
+    /// The entry in the old tree, if there is one.
+    pub old: Option<Stat>,
+    /// The entry in the new tree, if there is one.
+    pub new: Option<Stat>,
+}
+
+/// The changes that turn one tree into another, as returned by [`DTree::diff`]. The
+/// [`Display`](fmt::Display) implementation renders them in the style of a unified diff: a
+/// `--- a`, `+++ b` header, then one line per change, marked `+` if added, `-` if removed, and
+/// `~` if modified, followed by the aspects that differ. Paths of directories end in `/`.
+/// Lines are separated, not terminated, by newlines. The changes themselves are available
+/// for machine use, and with the `serde` feature can be serialized.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diff<'a> {
+    /// The changes, in pre-order with the entries of each directory sorted by name.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub changes: Vec<Change<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    /// True if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl<'a> fmt::Display for Diff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "--- a\n+++ b")?;
+        for c in &self.changes {
+            let mark = match c.kind {
+                ChangeKind::Added => '+',
+                ChangeKind::Removed => '-',
+                ChangeKind::Modified(_) => '~',
+            };
+            write!(f, "\n{} /{}", mark, c.path.join("/"))?;
+            let stat = c.new.or(c.old).expect("a change has an entry");
+            if stat.kind == NodeKind::Dir {
+                write!(f, "/")?;
+            }
+            if let ChangeKind::Modified(aspects) = &c.kind {
+                let names: Vec<&str> = aspects.iter().map(|a| a.name()).collect();
+                write!(f, " ({})", names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The aspects in which `old` and `new`, at the same path, differ.
+fn compare<'a>(old: &DEnt<'a>, new: &DEnt<'a>) -> Vec<Aspect> {
+    if old.same_node(new) {
+        return Vec::new();
+    }
+    let (o, n) = (old.inode.borrow(), new.inode.borrow());
+    let mut aspects = Vec::new();
+    let contents = match (&o.node, &n.node) {
+        (Node::Dir(_), Node::Dir(_)) => false,
+        (Node::File(a), Node::File(b)) => a != b,
+        (Node::Symlink(a), Node::Symlink(b)) => a != b,
+        _ => return vec![Aspect::Kind],
+    };
+    if o.meta.mode != n.meta.mode {
+        aspects.push(Aspect::Mode);
+    }
+    if (o.meta.uid, o.meta.gid) != (n.meta.uid, n.meta.gid) {
+        aspects.push(Aspect::Owner);
+    }
+    if !matches!(o.node, Node::Dir(_)) && o.meta.mtime != n.meta.mtime {
+        aspects.push(Aspect::Mtime);
+    }
+    if contents {
+        aspects.push(Aspect::Contents);
+    }
+    aspects
+}
+
+/// Add the changes from the directory `old` to the directory `new`, both at `path`, to
+/// `changes`.
+fn diff_dirs<'a>(
+    old: &DTree<'a>,
+    new: &DTree<'a>,
+    path: &mut Vec<&'a str>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    let names: BTreeSet<&'a str> = old
+        .children
+        .iter()
+        .chain(&new.children)
+        .map(|d| d.name)
+        .collect();
+    for name in names {
+        path.push(name);
+        let (o, n) = (old.child(name), new.child(name));
+        let kind = match (o, n) {
+            (Some(_), None) => Some(ChangeKind::Removed),
+            (None, Some(_)) => Some(ChangeKind::Added),
+            (Some(o), Some(n)) => {
+                let aspects = compare(o, n);
+                (!aspects.is_empty()).then_some(ChangeKind::Modified(aspects))
+            }
+            (None, None) => unreachable!("name is in one of the directories"),
+        };
+        if let Some(kind) = kind {
+            changes.push(Change {
+                path: path.clone(),
+                kind,
+                old: o.map(DEnt::stat),
+                new: n.map(DEnt::stat),
+            });
+        }
+        if let (Some(o), Some(n)) = (o, n) {
+            if !o.same_node(n) {
+                if let (Some(o), Some(n)) = (o.subdir(), n.subdir()) {
+                    diff_dirs(&o, &n, path, changes);
+                }
+            }
+        }
+        path.pop();
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Find the changes that turn this tree into `new`, matching entries by path. An added or
+    /// removed directory is reported once, without the entries in it. Symbolic links are
+    /// compared as links, not followed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{Aspect, ChangeKind, DTree, FakeClock};
+    /// # use std::time::Duration;
+    /// let clock = FakeClock::default();
+    /// let (old, new) = DTree::with_clock(clock.clone(), || {
+    ///     let mut old = DTree::new();
+    ///     old.mkdir_all(&["a", "b"]).unwrap();
+    ///     old.create("a/f").unwrap();
+    ///     old.mkdir("c").unwrap();
+    ///
+    ///     let mut new = old.clone();
+    ///     assert!(old.diff(&new).is_empty());
+    ///     clock.advance(Duration::from_secs(1));
+    ///     new.write("a/f", b"text").unwrap();
+    ///     new.chmod("a/f", 0o600).unwrap();
+    ///     new.remove_all("c").unwrap();
+    ///     new.mkdir_all(&["a", "d", "e"]).unwrap();
+    ///     new.symlink("a", "l").unwrap();
+    ///     (old, new)
+    /// });
+    ///
+    /// let diff = old.diff(&new);
+    /// let expected = "\
+    /// --- a
+    /// +++ b
+    /// + /a/d/
+    /// ~ /a/f (mode, mtime, contents)
+    /// - /c/
+    /// + /l";
+    /// assert_eq!(diff.to_string(), expected);
+    /// assert_eq!(diff.changes[0].path, ["a", "d"]);
+    /// assert_eq!(diff.changes[0].kind, ChangeKind::Added);
+    /// assert_eq!(
+    ///     diff.changes[1].kind,
+    ///     ChangeKind::Modified(vec![Aspect::Mode, Aspect::Mtime, Aspect::Contents]),
+    /// );
+    /// assert_eq!(diff.changes[1].new.unwrap().len, 4);
+    /// ```
+    pub fn diff(&self, new: &DTree<'a>) -> Diff<'a> {
+        let mut changes = Vec::new();
+        diff_dirs(self, new, &mut Vec::new(), &mut changes);
+        Diff { changes }
+    }
+}
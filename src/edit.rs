@@ -0,0 +1,506 @@
+//! Edit scripts: sequences of operations turning one tree into another, with renames
+//! detected by similarity.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use crate::{DPath, DTree, DirError, Node, NodeKind, OsState, RenameMode, Result};
+
+/// One operation of an [`EditScript`]. Paths are given as the component names leading to the
+/// entry from the root, borrowed from the trees the script was made from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edit<'a> {
+    /// Make an empty directory.
+    Mkdir(#[cfg_attr(feature = "serde", serde(borrow))] Vec<&'a str>),
+    /// Make an empty regular file.
+    Create(#[cfg_attr(feature = "serde", serde(borrow))] Vec<&'a str>),
+    /// Make a symbolic link referring to `target`.
+    Symlink {
+        target: &'a str,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        path: Vec<&'a str>,
+    },
+    /// Remove an entry together with everything below it.
+    Remove(#[cfg_attr(feature = "serde", serde(borrow))] Vec<&'a str>),
+    /// Move an entry, together with everything below it.
+    Rename {
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        from: Vec<&'a str>,
+        #[cfg_attr(feature = "serde", serde(borrow))]
+        to: Vec<&'a str>,
+    },
+}
+
+/// `path` written as an absolute path.
+fn absolute(path: &[&str]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+impl<'a> fmt::Display for Edit<'a> {
+    /// Write the edit as a shell command.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Mkdir(path) => write!(f, "mkdir {}", absolute(path)),
+            Edit::Create(path) => write!(f, "touch {}", absolute(path)),
+            Edit::Symlink { target, path } => write!(f, "ln -s {} {}", target, absolute(path)),
+            Edit::Remove(path) => write!(f, "rm -r {}", absolute(path)),
+            Edit::Rename { from, to } => write!(f, "mv {} {}", absolute(from), absolute(to)),
+        }
+    }
+}
+
+/// A sequence of edits turning one tree into another, as returned by [`DTree::edit_script`].
+/// The script reproduces the structure of the new tree, as [`DTree::paths`] lists it: new
+/// files are empty, and metadata is not carried over. The
+/// [`Display`](fmt::Display) implementation writes one shell command per line, separated,
+/// not terminated, by newlines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditScript<'a> {
+    /// The edits, in the order they are to be made.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub edits: Vec<Edit<'a>>,
+}
+
+impl<'a> fmt::Display for EditScript<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, e) in self.edits.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the entry at `path`, by which errors refer to it.
+fn name<'a>(path: &[&'a str]) -> &'a str {
+    path.last().copied().unwrap_or("/")
+}
+
+impl<'a> EditScript<'a> {
+    /// Make the edits of this script, in order, on `state`. Paths are absolute, so the
+    /// working directory does not matter, unless an edit would remove it; if it is moved, it
+    /// follows the move. New entries borrow their names from the trees the script was made
+    /// from, not from the script, which need not be kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, OsState};
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.create("a/f").unwrap();
+    /// let new = DTree::from_paths(&["/b/f", "/c/"]).unwrap();
+    /// s.dtree.edit_script(&new).replay(&mut s).unwrap();
+    /// assert_eq!(&s.paths().unwrap(), &["/b/f", "/c/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * The first error any edit gives, as for [`OsState::create`], which also covers making
+    ///   directories, [`OsState::symlink`], [`OsState::remove_all`] or [`OsState::rename`]
+    ///   with `RenameMode::NoReplace`, naming the entry by its last component. The edits
+    ///   before it have been made.
+    pub fn replay(&self, state: &mut OsState<'a>) -> Result<'a, ()> {
+        for e in &self.edits {
+            let now = state.clock.now();
+            match e {
+                Edit::Mkdir(path) => {
+                    let dir = Node::Dir(DTree::new());
+                    state.dtree.add_entry(name(path), path, dir, now)?;
+                }
+                Edit::Create(path) => state.dtree.create_file(name(path), path, now)?,
+                Edit::Symlink { target, path } => {
+                    DPath::parse(target)?;
+                    let link = Node::Symlink(target);
+                    state.dtree.add_entry(name(path), path, link, now)?;
+                }
+                Edit::Remove(path) => {
+                    if state.cwd.starts_with(path) {
+                        return Err(DirError::Busy(name(path)));
+                    }
+                    state.dtree.unlink(name(path), path, true, now)?;
+                }
+                Edit::Rename { from, to } => {
+                    if from != to && state.cwd.starts_with(to) {
+                        return Err(DirError::Busy(name(to)));
+                    }
+                    let mode = RenameMode::NoReplace;
+                    state
+                        .dtree
+                        .move_entry(name(from), from, name(to), to, mode, now)?;
+                    if state.cwd.starts_with(from) {
+                        let rest = state.cwd.split_off(from.len());
+                        state.cwd = to.clone();
+                        state.cwd.extend(rest);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A path as a list of component names.
+type Path<'a> = Vec<&'a str>;
+
+/// Names for the directory at the top of the tree that entries are moved aside into while
+/// the script runs, the first not used as a name anywhere in either tree being taken.
+const HOLDING: [&str; 8] = [
+    ".dtree-edit",
+    ".dtree-edit-1",
+    ".dtree-edit-2",
+    ".dtree-edit-3",
+    ".dtree-edit-4",
+    ".dtree-edit-5",
+    ".dtree-edit-6",
+    ".dtree-edit-7",
+];
+
+/// An entry of a tree being edited.
+#[derive(Debug)]
+struct Entry<'a> {
+    kind: NodeKind,
+    /// The contents of a file.
+    contents: Option<Vec<u8>>,
+    /// The target of a symbolic link.
+    target: Option<&'a str>,
+}
+
+/// Every entry of `dt` by path, in pre-order with the entries of each directory sorted by
+/// name.
+fn entries<'a>(dt: &DTree<'a>) -> BTreeMap<Path<'a>, Entry<'a>> {
+    dt.walk()
+        .include_dirs(true)
+        .map(|w| {
+            let node = w.entry.node();
+            let entry = Entry {
+                kind: w.entry.kind(),
+                contents: match &*node {
+                    Node::File(contents) => Some(contents.clone()),
+                    _ => None,
+                },
+                target: w.entry.link_target(),
+            };
+            (w.path, entry)
+        })
+        .collect()
+}
+
+/// The paths below `path` in `entries`, relative to `path`.
+fn below<'e, 'a>(
+    entries: &'e BTreeMap<Path<'a>, Entry<'a>>,
+    path: &'e [&'a str],
+) -> impl Iterator<Item = &'e [&'a str]> {
+    entries
+        .range(path.to_vec()..)
+        .skip(1)
+        .take_while(move |(p, _)| p.starts_with(path))
+        .map(move |(p, _)| &p[path.len()..])
+}
+
+/// `path` with `rest` appended.
+fn join<'a>(path: &[&'a str], rest: &[&'a str]) -> Path<'a> {
+    path.iter().chain(rest).copied().collect()
+}
+
+/// How much is saved by making the old entry `a` into the new entry `b`, if it is worth
+/// doing: one for a rename, or two for keeping an entry where it is, and one for each entry
+/// below them that would then be in place. Entries kept in place need not have the same
+/// contents.
+fn benefit<'a>(
+    old: &BTreeMap<Path<'a>, Entry<'a>>,
+    new: &BTreeMap<Path<'a>, Entry<'a>>,
+    a: &[&'a str],
+    b: &[&'a str],
+) -> Option<usize> {
+    let (ea, eb) = (&old[a], &new[b]);
+    if ea.kind != eb.kind {
+        return None;
+    }
+    let kept = a == b;
+    let base = if kept { 2 } else { 1 };
+    match ea.kind {
+        NodeKind::File => (kept || ea.contents == eb.contents).then_some(base),
+        NodeKind::Symlink => (kept || ea.target == eb.target).then_some(base),
+        NodeKind::Dir => {
+            let mut inside = 0;
+            let mut shared = 0;
+            for r in below(old, a) {
+                inside += 1;
+                let kind = new.get(&join(b, r)).map(|e| e.kind);
+                if kind == Some(old[&join(a, r)].kind) {
+                    shared += 1;
+                }
+            }
+            (kept || shared > 0 || inside == 0).then_some(base + shared)
+        }
+    }
+}
+
+/// The state of an edit script being made.
+struct Editor<'e, 'a> {
+    old: &'e BTreeMap<Path<'a>, Entry<'a>>,
+    /// Old entries matched to the new entries they become.
+    matched: HashMap<Path<'a>, Path<'a>>,
+    /// Old entries that have been moved, and where to.
+    moved: BTreeMap<Path<'a>, Path<'a>>,
+    /// Old entries that have been removed.
+    removed: BTreeSet<Path<'a>>,
+    /// The name of the holding directory for entries moved aside, if there is one free.
+    holding: Option<&'static str>,
+    /// The names used in each level of the holding directory, the next level being the
+    /// directory of the same name inside it.
+    levels: Vec<BTreeSet<&'a str>>,
+    edits: Vec<Edit<'a>>,
+}
+
+impl<'e, 'a> Editor<'e, 'a> {
+    /// Where the old entry `a` is now.
+    fn current(&self, a: &[&'a str]) -> Path<'a> {
+        for n in (1..=a.len()).rev() {
+            if let Some(to) = self.moved.get(&a[..n]) {
+                return join(to, &a[n..]);
+            }
+        }
+        a.to_vec()
+    }
+
+    /// True if the old entry `a` has been removed, by itself or with an entry it was below
+    /// at the time.
+    fn gone(&self, a: &[&'a str]) -> bool {
+        for n in (1..=a.len()).rev() {
+            if self.removed.contains(&a[..n]) {
+                return true;
+            }
+            if self.moved.contains_key(&a[..n]) {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// The old entry now at `path`, if there is one.
+    fn occupant(&self, path: &[&'a str]) -> Option<Path<'a>> {
+        let a = self
+            .moved
+            .iter()
+            .filter(|(_, to)| path.starts_with(to))
+            .max_by_key(|(_, to)| to.len())
+            .map_or_else(|| path.to_vec(), |(from, to)| join(from, &path[to.len()..]));
+        (self.old.contains_key(&a) && !self.gone(&a) && self.current(&a) == path).then_some(a)
+    }
+
+    /// True if an entry below the old entry `a` is matched to a new entry.
+    fn keeps_below(&self, a: &[&'a str]) -> bool {
+        below(self.old, a).any(|r| self.matched.contains_key(&join(a, r)))
+    }
+
+    /// Remove the old entry `a`.
+    fn remove(&mut self, a: &[&'a str]) {
+        self.edits.push(Edit::Remove(self.current(a)));
+        self.removed.insert(a.to_vec());
+    }
+
+    /// Move the old entry `a` to `to`.
+    fn rename(&mut self, a: &[&'a str], to: Path<'a>) {
+        let from = self.current(a);
+        self.edits.push(Edit::Rename {
+            from,
+            to: to.clone(),
+        });
+        self.moved.insert(a.to_vec(), to);
+    }
+
+    /// Clear `path` for a new entry, removing what is there if nothing in it is kept, and
+    /// otherwise moving it aside, under its own name, into the first level of the holding
+    /// directory where that name is unused.
+    fn evict(&mut self, path: &[&'a str]) {
+        let a = match self.occupant(path) {
+            Some(a) => a,
+            None => return,
+        };
+        if !self.matched.contains_key(&a) && !self.keeps_below(&a) {
+            self.remove(&a);
+            return;
+        }
+        let holding = self
+            .holding
+            .expect("an unused name for the holding directory");
+        let name = *a.last().expect("the root is never evicted");
+        let level = match self.levels.iter().position(|l| !l.contains(name)) {
+            Some(level) => level,
+            None => {
+                let dir = vec![holding; self.levels.len() + 1];
+                self.edits.push(Edit::Mkdir(dir));
+                self.levels.push(std::iter::once(holding).collect());
+                self.levels.len() - 1
+            }
+        };
+        self.levels[level].insert(name);
+        let mut to = vec![holding; level + 1];
+        to.push(name);
+        self.rename(&a, to);
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Find a short sequence of edits turning this tree into `new`. Entries are kept in place
+    /// when the same kind of entry is at the same path in both trees, and renamed when they
+    /// are similar enough: files with the same contents, symbolic links with the same target,
+    /// and directories sharing entries at the same paths below them, or empty ones. Where
+    /// there is a choice, whatever saves the most edits is chosen first, so a directory may
+    /// be moved over one kept in place. An entry in the way of another is moved aside into a
+    /// holding directory at the top of the tree, named `.dtree-edit`, or `.dtree-edit-N` if
+    /// that name is used in either tree, which is removed at the end. The script borrows its
+    /// names from the two trees. Symbolic links are not followed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, OsState};
+    /// let mut old = DTree::new();
+    /// old.mkdir_all(&["src", "bin"]).unwrap();
+    /// old.create("src/bin/main.rs").unwrap();
+    /// old.create("src/lib.rs").unwrap();
+    /// old.mkdir("tmp").unwrap();
+    /// old.create("tmp/junk").unwrap();
+    ///
+    /// let mut new = DTree::new();
+    /// new.mkdir("src").unwrap();
+    /// new.create("src/lib.rs").unwrap();
+    /// new.mkdir_all(&["app", "cli"]).unwrap();
+    /// new.create("app/cli/main.rs").unwrap();
+    /// new.mkdir("docs").unwrap();
+    ///
+    /// let script = old.edit_script(&new);
+    /// let expected = "\
+    /// rm -r /tmp
+    /// mkdir /app
+    /// mv /src/bin /app/cli
+    /// mkdir /docs";
+    /// assert_eq!(script.to_string(), expected);
+    ///
+    /// let mut s = OsState::new();
+    /// s.dtree = old.clone();
+    /// script.replay(&mut s).unwrap();
+    /// let (mut got, mut want) = (s.paths().unwrap(), new.paths());
+    /// got.sort();
+    /// want.sort();
+    /// assert_eq!(got, want);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if an entry must be moved aside and `.dtree-edit` and `.dtree-edit-1` through
+    /// `.dtree-edit-7` are all used as names in the trees.
+    pub fn edit_script(&self, new: &DTree<'a>) -> EditScript<'a> {
+        let old = entries(self);
+        let new = entries(new);
+        let in_place = |p: &Path<'a>| {
+            let kinds = (old.get(p).map(|e| e.kind), new.get(p).map(|e| e.kind));
+            kinds.0.is_some() && kinds.0 == kinds.1
+        };
+        let mut candidates = Vec::new();
+        for a in old.keys() {
+            if in_place(a) {
+                candidates.push((a, a));
+                candidates.extend(new.keys().filter(|b| !in_place(b)).map(|b| (a, b)));
+            } else {
+                candidates.extend(new.keys().map(|b| (a, b)));
+            }
+        }
+        let mut pairs = Vec::new();
+        for (a, b) in candidates {
+            if let Some(benefit) = benefit(&old, &new, a, b) {
+                let renamed = a.last() != b.last();
+                pairs.push((std::cmp::Reverse(benefit), renamed, a, b));
+            }
+        }
+        pairs.sort();
+
+        let mut matched: HashMap<Path<'a>, Path<'a>> = HashMap::new();
+        let mut matched_new: BTreeSet<Path<'a>> = BTreeSet::new();
+        for (_, _, a, b) in pairs {
+            if matched.contains_key(a) || matched_new.contains(b) {
+                continue;
+            }
+            matched.insert(a.clone(), b.clone());
+            matched_new.insert(b.clone());
+            for r in below(&old, a) {
+                let (a, b) = (join(a, r), join(b, r));
+                let same = new.get(&b).is_some_and(|e| e.kind == old[&a].kind);
+                if same && !matched.contains_key(&a) && !matched_new.contains(&b) {
+                    matched.insert(a, b.clone());
+                    matched_new.insert(b);
+                }
+            }
+        }
+
+        let used = |name: &&str| old.keys().chain(new.keys()).flatten().any(|c| c == name);
+        let holding = HOLDING.iter().copied().find(|name| !used(name));
+        let mut editor = Editor {
+            old: &old,
+            matched,
+            moved: BTreeMap::new(),
+            removed: BTreeSet::new(),
+            holding,
+            levels: Vec::new(),
+            edits: Vec::new(),
+        };
+
+        let garbage: Vec<&Path<'a>> = old
+            .keys()
+            .filter(|a| !editor.matched.contains_key(*a))
+            .filter(|a| a.len() == 1 || editor.matched.contains_key(&a[..a.len() - 1]))
+            .collect();
+        for &a in &garbage {
+            if !editor.keeps_below(a) {
+                editor.remove(a);
+            }
+        }
+
+        let sources: HashMap<Path<'a>, Path<'a>> = editor
+            .matched
+            .iter()
+            .map(|(a, b)| (b.clone(), a.clone()))
+            .collect();
+        for (b, e) in &new {
+            match sources.get(b) {
+                Some(a) => {
+                    if &editor.current(a) != b {
+                        editor.evict(b);
+                        editor.rename(a, b.clone());
+                    }
+                }
+                None => {
+                    editor.evict(b);
+                    let path = b.clone();
+                    editor.edits.push(match e.kind {
+                        NodeKind::Dir => Edit::Mkdir(path),
+                        NodeKind::File => Edit::Create(path),
+                        NodeKind::Symlink => Edit::Symlink {
+                            target: e.target.expect("a symbolic link has a target"),
+                            path,
+                        },
+                    });
+                }
+            }
+        }
+
+        for &a in &garbage {
+            if !editor.gone(a) && Some(editor.current(a)[0]) != holding {
+                editor.remove(a);
+            }
+        }
+        if let Some(holding) = holding.filter(|_| !editor.levels.is_empty()) {
+            editor.edits.push(Edit::Remove(vec![holding]));
+        }
+        EditScript {
+            edits: editor.edits,
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Glob patterns matched lazily against the entries of a tree.
+
+use thiserror::Error;
+
+use crate::{DEnt, DTree, OsState, Result, WalkEntry};
+
+/// Why a glob pattern could not be parsed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GlobError {
+    /// The pattern was empty.
+    #[error("empty glob pattern")]
+    Empty,
+    /// A `[` at the given character position had no matching `]`.
+    #[error("unclosed character class at {0}")]
+    UnclosedClass(usize),
+}
+
+/// Part of a pattern for one component name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// This character.
+    Char(char),
+    /// `?`: any one character.
+    One,
+    /// `*`: any run of characters.
+    Any,
+    /// `[...]`: one character in, or with `negated` not in, one of the inclusive ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// The pattern for one component of a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Component {
+    /// A name with no wildcards.
+    Literal(String),
+    /// A name with wildcards.
+    Pattern(Vec<Token>),
+    /// `**`: any number of components, including none.
+    AnyDepth,
+}
+
+/// True if `tokens` match all of `name`.
+fn matches_name(tokens: &[Token], name: &[char]) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((Token::Any, rest)) => (0..=name.len()).any(|i| matches_name(rest, &name[i..])),
+        Some((t, rest)) => match name.split_first() {
+            None => false,
+            Some((&c, name)) => {
+                let ok = match t {
+                    Token::Char(tc) => *tc == c,
+                    Token::One => true,
+                    Token::Class { negated, ranges } => {
+                        ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+                    }
+                    Token::Any => unreachable!("handled above"),
+                };
+                ok && matches_name(rest, name)
+            }
+        },
+    }
+}
+
+/// Parse the pattern `text` for one component, whose first character is at position `at` in
+/// the whole pattern.
+fn parse_component(text: &str, at: usize) -> std::result::Result<Component, GlobError> {
+    if text == "**" {
+        return Ok(Component::AnyDepth);
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let token = match chars[i] {
+            '*' => Token::Any,
+            '?' => Token::One,
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                Token::Char(chars[i])
+            }
+            '[' => {
+                let start = i;
+                i += 1;
+                let negated = matches!(chars.get(i), Some('!' | '^'));
+                if negated {
+                    i += 1;
+                }
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let c = *chars.get(i).ok_or(GlobError::UnclosedClass(at + start))?;
+                    if c == ']' && !first {
+                        break;
+                    }
+                    first = false;
+                    match (chars.get(i + 1), chars.get(i + 2)) {
+                        (Some('-'), Some(&hi)) if hi != ']' => {
+                            ranges.push((c, hi));
+                            i += 3;
+                        }
+                        _ => {
+                            ranges.push((c, c));
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+        i += 1;
+    }
+    let literal: Option<String> = tokens
+        .iter()
+        .map(|t| match t {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    Ok(match literal {
+        Some(name) => Component::Literal(name),
+        None => Component::Pattern(tokens),
+    })
+}
+
+/// A parsed glob pattern, matched against the component names of paths. `*` matches any
+/// run of characters within a name, `?` any one character, and `[abc]`, `[a-z]` or `[!abc]`
+/// any one character in or not in the class; `\` makes the character after it literal. A
+/// component that is just `**` matches any number of components, including none. A leading
+/// `/` makes the pattern absolute, and leading `.` and `..` components move up from where
+/// the pattern is matched, staying at the top of the tree; elsewhere they match nothing.
+/// Wildcards match names starting with `.` like any other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    absolute: bool,
+    /// How many directories to go up from where the pattern is matched.
+    ups: usize,
+    components: Vec<Component>,
+}
+
+impl Glob {
+    /// Parse `pattern`. Empty components, as in `a//b`, are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{Glob, GlobError};
+    /// let g = Glob::new("src/**/[a-m]*.rs").unwrap();
+    /// assert!(g.matches(&["src", "lib.rs"]));
+    /// assert!(g.matches(&["src", "bin", "x", "main.rs"]));
+    /// assert!(!g.matches(&["src", "bin", "x", "parse.rs"]));
+    /// assert!(!g.matches(&["tests", "lib.rs"]));
+    /// assert_eq!(Glob::new("a/[bc"), Err(GlobError::UnclosedClass(2)));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `GlobError::Empty` if `pattern` is empty.
+    /// * `GlobError::UnclosedClass` if a `[` has no matching `]`.
+    pub fn new(pattern: &str) -> std::result::Result<Self, GlobError> {
+        if pattern.is_empty() {
+            return Err(GlobError::Empty);
+        }
+        let mut ups = 0;
+        let mut components = Vec::new();
+        let mut at = 0;
+        for text in pattern.split('/') {
+            match text {
+                "" => (),
+                "." if components.is_empty() => (),
+                ".." if components.is_empty() => ups += 1,
+                _ => components.push(parse_component(text, at)?),
+            }
+            at += text.chars().count() + 1;
+        }
+        Ok(Glob {
+            absolute: pattern.starts_with('/'),
+            ups,
+            components,
+        })
+    }
+
+    /// True if the pattern starts with `/`.
+    pub fn is_absolute(&self) -> bool {
+        self.absolute
+    }
+
+    /// True if the pattern, less any leading `.` and `..` components, matches the component
+    /// names `path`.
+    pub fn matches(&self, path: &[&str]) -> bool {
+        let mut states = self.start();
+        for name in path {
+            states = self.step(&states, name);
+        }
+        states.contains(&self.components.len())
+    }
+
+    /// The states before any component is matched.
+    fn start(&self) -> Vec<usize> {
+        self.close(vec![0])
+    }
+
+    /// `states`, with the states after each `**` matching no components added.
+    fn close(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut i = 0;
+        while i < states.len() {
+            let s = states[i];
+            if self.components.get(s) == Some(&Component::AnyDepth) && !states.contains(&(s + 1)) {
+                states.push(s + 1);
+            }
+            i += 1;
+        }
+        states.sort_unstable();
+        states
+    }
+
+    /// The states after matching the component `name` from `states`.
+    fn step(&self, states: &[usize], name: &str) -> Vec<usize> {
+        let chars: Vec<char> = name.chars().collect();
+        let mut next = Vec::new();
+        for &s in states {
+            let to = match self.components.get(s) {
+                Some(Component::AnyDepth) => Some(s),
+                Some(Component::Literal(l)) if l == name => Some(s + 1),
+                Some(Component::Pattern(tokens)) if matches_name(tokens, &chars) => Some(s + 1),
+                _ => None,
+            };
+            if let Some(to) = to {
+                if !next.contains(&to) {
+                    next.push(to);
+                }
+            }
+        }
+        self.close(next)
+    }
+
+    /// The names that can be matched from `states`, if they are all literal.
+    fn literals(&self, states: &[usize]) -> Option<Vec<&str>> {
+        states
+            .iter()
+            .filter(|&&s| s < self.components.len())
+            .map(|&s| match &self.components[s] {
+                Component::Literal(l) => Some(l.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// An entry waiting to be looked at.
+#[derive(Debug)]
+struct Pending<'a> {
+    path: Vec<&'a str>,
+    entry: DEnt<'a>,
+    /// The states after matching the entry's name.
+    states: Vec<usize>,
+}
+
+/// A lazy iterator over the entries matching a [`Glob`], as returned by [`DTree::glob`] and
+/// [`OsState::glob`]. Entries are visited depth-first, each directory before the entries in
+/// it, and each path is given from the top of the tree. Directories whose paths cannot lead
+/// to a match are not looked into, and where the pattern allows only literal names next,
+/// only those names are looked up. Symbolic links are not followed.
+#[derive(Debug)]
+pub struct Globbed<'t, 'a> {
+    glob: Glob,
+    /// The tree, and the directory in it to start from, until the first entry is asked for.
+    root: Option<(&'t DTree<'a>, Vec<&'a str>)>,
+    pending: Vec<Pending<'a>>,
+}
+
+impl<'t, 'a> Globbed<'t, 'a> {
+    /// Queue the entries of `dt`, which is at `path`, that can lead to a match from
+    /// `states`.
+    fn queue(&mut self, dt: &DTree<'a>, path: &[&'a str], states: &[usize]) {
+        let children: Vec<&DEnt<'a>> = match self.glob.literals(states) {
+            Some(names) => names.iter().filter_map(|n| dt.child(n)).collect(),
+            None => dt.children.iter().collect(),
+        };
+        for d in children.into_iter().rev() {
+            let next = self.glob.step(states, d.name);
+            if next.is_empty() {
+                continue;
+            }
+            let mut path = path.to_vec();
+            path.push(d.name);
+            self.pending.push(Pending {
+                path,
+                entry: d.share(),
+                states: next,
+            });
+        }
+    }
+}
+
+impl<'t, 'a> Iterator for Globbed<'t, 'a> {
+    type Item = WalkEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((dt, start)) = self.root.take() {
+            let states = self.glob.start();
+            // A missing start directory matches nothing.
+            let _ = dt.in_dir(&start, |dt| self.queue(dt, &start, &states));
+        }
+        let end = self.glob.components.len();
+        while let Some(p) = self.pending.pop() {
+            if p.states.iter().any(|&s| s < end) {
+                if let Some(dt) = p.entry.subdir() {
+                    self.queue(&dt, &p.path, &p.states);
+                }
+            }
+            if p.states.contains(&end) {
+                return Some(WalkEntry {
+                    depth: p.path.len(),
+                    path: p.path,
+                    entry: p.entry,
+                });
+            }
+        }
+        None
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Start a lazy search for the entries below this directory whose paths match `glob`.
+    /// See [`Globbed`] for the order and paths of the entries found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, Glob};
+    /// let dt = DTree::from_paths(&["/src/lib.rs", "/src/bin/main.rs", "/src/b/", "/README"])
+    ///     .unwrap();
+    /// let found = |pattern| {
+    ///     dt.glob(&Glob::new(pattern).unwrap())
+    ///         .map(|w| w.path.join("/"))
+    ///         .collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(found("src/*.rs"), ["src/lib.rs"]);
+    /// assert_eq!(found("**/*.rs"), ["src/lib.rs", "src/bin/main.rs"]);
+    /// assert_eq!(found("src/b*"), ["src/bin", "src/b"]);
+    /// assert_eq!(found("src/b?*"), ["src/bin"]);
+    /// assert_eq!(found("[!s]*"), ["README"]);
+    /// assert_eq!(found("/src/**"), ["src", "src/lib.rs", "src/bin", "src/bin/main.rs", "src/b"]);
+    /// ```
+    pub fn glob(&self, glob: &Glob) -> Globbed<'_, 'a> {
+        Globbed {
+            glob: glob.clone(),
+            root: Some((self, Vec::new())),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'a> OsState<'a> {
+    /// Start a lazy search for the entries whose paths, relative to the current working
+    /// directory, match `glob`, as with [`DTree::glob`]. Paths are given from the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{Glob, OsState};
+    /// let mut s = OsState::new();
+    /// s.mkdir_all(&["a", "b"]).unwrap();
+    /// s.mkdir_all(&["c", "d"]).unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// let found: Vec<_> = s
+    ///     .glob(&Glob::new("../*/?").unwrap())
+    ///     .unwrap()
+    ///     .map(|w| w.path.join("/"))
+    ///     .collect();
+    /// assert_eq!(found, ["a/b", "c/d"]);
+    /// let found: Vec<_> = s.glob(&Glob::new("*").unwrap()).unwrap().map(|w| w.path).collect();
+    /// assert_eq!(found, [["a", "b"]]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the current working directory is invalid.
+    /// * `DirError::NotADirectory` if a component of the current working directory is not
+    ///   a directory.
+    pub fn glob(&self, glob: &Glob) -> Result<'a, Globbed<'_, 'a>> {
+        let mut start = if glob.is_absolute() {
+            Vec::new()
+        } else {
+            self.dtree.in_dir(&self.cwd, |_| ())?;
+            self.cwd.clone()
+        };
+        start.truncate(start.len().saturating_sub(glob.ups));
+        Ok(Globbed {
+            glob: glob.clone(),
+            root: Some((&self.dtree, start)),
+            pending: Vec::new(),
+        })
+    }
+}
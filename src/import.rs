@@ -0,0 +1,314 @@
+//! Importing a tree from a real directory with `std::fs`.
+//!
+//! A [`DTree`] borrows its names, so a scan is kept in an [`FsTree`] that owns them, and
+//! trees are built borrowing from that.
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::{DEnt, DTree, Inode, Metadata, Node, DIR_MODE, FILE_MODE, SYMLINK_MODE};
+
+/// What [`FsTree::scan`] does with symbolic links.
+///
+/// # Examples
+///
+/// Followed links are imported as what they refer to, here a copy of `a` under `l`, while
+/// the link from `a` back up to the directory being scanned stays a link.
+///
+/// ```
+/// # #[cfg(unix)]
+/// # {
+/// # use dtree::{FsTree, ImportOptions, SymlinkMode};
+/// # use std::{fs, os::unix::fs::symlink};
+/// let root = std::env::temp_dir().join(format!("dtree-follow-{}", std::process::id()));
+/// fs::create_dir_all(root.join("a")).unwrap();
+/// fs::write(root.join("a/f"), b"").unwrap();
+/// symlink("..", root.join("a/up")).unwrap();
+/// symlink("a", root.join("l")).unwrap();
+///
+/// let options = ImportOptions::new().symlinks(SymlinkMode::Follow);
+/// let scan = FsTree::scan(&root, &options).unwrap();
+/// let dt = scan.tree();
+/// assert_eq!(&dt.paths_nofollow(), &["/a/f", "/a/up", "/l/f", "/l/up"]);
+/// assert_eq!(dt.readlink("l/up").unwrap(), "..");
+/// # fs::remove_dir_all(&root).unwrap();
+/// # }
+/// ```
+///
+/// Skipped links are left out altogether.
+///
+/// ```
+/// # #[cfg(unix)]
+/// # {
+/// # use dtree::{FsTree, ImportOptions, SymlinkMode};
+/// # use std::{fs, os::unix::fs::symlink};
+/// let root = std::env::temp_dir().join(format!("dtree-skip-{}", std::process::id()));
+/// fs::create_dir_all(root.join("a")).unwrap();
+/// fs::write(root.join("a/f"), b"").unwrap();
+/// symlink("..", root.join("a/up")).unwrap();
+/// symlink("a", root.join("l")).unwrap();
+///
+/// let options = ImportOptions::new().symlinks(SymlinkMode::Skip);
+/// let scan = FsTree::scan(&root, &options).unwrap();
+/// assert_eq!(&scan.tree().paths_nofollow(), &["/a/f"]);
+/// # fs::remove_dir_all(&root).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkMode {
+    /// Import what the link refers to in its place. A link back to a directory being scanned
+    /// is kept as a link, as are links that cannot be followed.
+    Follow,
+    /// Import the link itself, with its target.
+    Keep,
+    /// Leave the link out.
+    Skip,
+}
+
+/// A predicate on the real paths of entries, true for those to leave out.
+type Ignore = Box<dyn Fn(&Path) -> bool>;
+
+/// Options for [`FsTree::scan`]. By default symbolic links are kept as links, every depth is
+/// scanned, and nothing is left out.
+pub struct ImportOptions {
+    symlinks: SymlinkMode,
+    max_depth: Option<usize>,
+    skip_hidden: bool,
+    ignore: Option<Ignore>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            symlinks: SymlinkMode::Keep,
+            max_depth: None,
+            skip_hidden: false,
+            ignore: None,
+        }
+    }
+}
+
+impl fmt::Debug for ImportOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportOptions")
+            .field("symlinks", &self.symlinks)
+            .field("max_depth", &self.max_depth)
+            .field("skip_hidden", &self.skip_hidden)
+            .field("ignore", &self.ignore.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl ImportOptions {
+    /// The default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat symbolic links as `mode` says.
+    pub fn symlinks(mut self, mode: SymlinkMode) -> Self {
+        self.symlinks = mode;
+        self
+    }
+
+    /// Do not scan below `depth`: directories at `depth` are imported empty. Entries in the
+    /// directory scanned have depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Leave out entries whose names start with `.`, if `skip` is set.
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Leave out entries whose real paths `ignore` is true for, and everything below them.
+    pub fn ignore<F: Fn(&Path) -> bool + 'static>(mut self, ignore: F) -> Self {
+        self.ignore = Some(Box::new(ignore));
+        self
+    }
+}
+
+/// A scanned entry, owning its name.
+#[derive(Debug, Clone)]
+struct Scanned {
+    name: String,
+    node: ScannedNode,
+    meta: Metadata,
+}
+
+#[derive(Debug, Clone)]
+enum ScannedNode {
+    Dir(Vec<Scanned>),
+    File,
+    Symlink(String),
+}
+
+/// The structure of a real directory, scanned with [`FsTree::scan`], owning the names that
+/// trees built from it with [`FsTree::tree`] borrow. Files are imported empty; permission
+/// bits, ownership and timestamps are imported where the platform provides them.
+#[derive(Debug, Clone)]
+pub struct FsTree {
+    entries: Vec<Scanned>,
+}
+
+impl FsTree {
+    /// Scan the directory `root` and everything below it as `options` say. Entries of each
+    /// directory are sorted by name; names that are not UTF-8 are converted lossily.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{FsTree, ImportOptions};
+    /// # use std::fs;
+    /// let root = std::env::temp_dir().join(format!("dtree-import-{}", std::process::id()));
+    /// fs::create_dir_all(root.join("a/b/c")).unwrap();
+    /// fs::create_dir_all(root.join(".git/objects")).unwrap();
+    /// fs::create_dir_all(root.join("target/debug")).unwrap();
+    /// fs::write(root.join("a/f"), b"data").unwrap();
+    ///
+    /// let options = ImportOptions::new()
+    ///     .skip_hidden(true)
+    ///     .max_depth(2)
+    ///     .ignore(|path| path.ends_with("target"));
+    /// let scan = FsTree::scan(&root, &options).unwrap();
+    /// let dt = scan.tree();
+    /// assert_eq!(&dt.paths(), &["/a/b/", "/a/f"]);
+    /// # fs::remove_dir_all(&root).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * Any error reading `root` or a directory below it, or their metadata.
+    pub fn scan(root: impl AsRef<Path>, options: &ImportOptions) -> io::Result<Self> {
+        let root = root.as_ref();
+        let mut scanning = vec![fs::canonicalize(root)?];
+        let entries = scan_dir(root, 1, options, &mut scanning)?;
+        Ok(FsTree { entries })
+    }
+
+    /// Build a tree of the scanned entries, borrowing their names.
+    pub fn tree(&self) -> DTree<'_> {
+        build(&self.entries)
+    }
+}
+
+/// Scan the directory at `path`, whose entries are at `depth`. `scanning` holds the
+/// canonical paths of the directories being scanned, ending with that of `path`.
+fn scan_dir(
+    path: &Path,
+    depth: usize,
+    options: &ImportOptions,
+    scanning: &mut Vec<PathBuf>,
+) -> io::Result<Vec<Scanned>> {
+    if options.max_depth.is_some_and(|max| depth > max) {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for dirent in fs::read_dir(path)? {
+        let dirent = dirent?;
+        let file_name = dirent.file_name();
+        let name = file_name.to_string_lossy().into_owned();
+        let path = dirent.path();
+        if options.skip_hidden && name.starts_with('.') {
+            continue;
+        }
+        if options.ignore.as_ref().is_some_and(|ignore| ignore(&path)) {
+            continue;
+        }
+        let lmeta = fs::symlink_metadata(&path)?;
+        let (node, meta) = if lmeta.file_type().is_symlink() {
+            match options.symlinks {
+                SymlinkMode::Skip => continue,
+                SymlinkMode::Keep => (symlink(&path)?, &lmeta),
+                SymlinkMode::Follow => {
+                    let target = fs::canonicalize(&path).ok();
+                    let meta = fs::metadata(&path).ok();
+                    match (target, meta) {
+                        (Some(target), Some(meta)) if !scanning.contains(&target) => {
+                            let node = if meta.is_dir() {
+                                scanning.push(target);
+                                let entries = scan_dir(&path, depth + 1, options, scanning);
+                                scanning.pop();
+                                ScannedNode::Dir(entries?)
+                            } else {
+                                ScannedNode::File
+                            };
+                            entries.push(scanned(name, node, &meta));
+                            continue;
+                        }
+                        _ => (symlink(&path)?, &lmeta),
+                    }
+                }
+            }
+        } else if lmeta.is_dir() {
+            let canonical = scanning[scanning.len() - 1].join(&file_name);
+            scanning.push(canonical);
+            let entries = scan_dir(&path, depth + 1, options, scanning);
+            scanning.pop();
+            (ScannedNode::Dir(entries?), &lmeta)
+        } else {
+            (ScannedNode::File, &lmeta)
+        };
+        entries.push(scanned(name, node, meta));
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// The symbolic link at `path`.
+fn symlink(path: &Path) -> io::Result<ScannedNode> {
+    let target = fs::read_link(path)?;
+    Ok(ScannedNode::Symlink(target.to_string_lossy().into_owned()))
+}
+
+/// A scanned entry named `name` referring to `node`, with metadata taken from `fs_meta`.
+fn scanned(name: String, node: ScannedNode, fs_meta: &fs::Metadata) -> Scanned {
+    let mode = match node {
+        ScannedNode::Dir(_) => DIR_MODE,
+        ScannedNode::File => FILE_MODE,
+        ScannedNode::Symlink(_) => SYMLINK_MODE,
+    };
+    let mtime = fs_meta.modified().unwrap_or_else(|_| SystemTime::now());
+    let mut meta = Metadata::new(mode, mtime);
+    meta.atime = fs_meta.accessed().unwrap_or(mtime);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        meta.mode = fs_meta.mode() & 0o7777;
+        meta.uid = fs_meta.uid();
+        meta.gid = fs_meta.gid();
+        if let Ok(secs) = u64::try_from(fs_meta.ctime()) {
+            let nanos = fs_meta.ctime_nsec() as u32;
+            meta.ctime = SystemTime::UNIX_EPOCH + std::time::Duration::new(secs, nanos);
+        }
+    }
+    Scanned { name, node, meta }
+}
+
+/// Build a directory of `entries`, borrowing their names.
+fn build(entries: &[Scanned]) -> DTree<'_> {
+    let children = entries
+        .iter()
+        .map(|s| {
+            let node = match &s.node {
+                ScannedNode::Dir(entries) => Node::Dir(build(entries)),
+                ScannedNode::File => Node::File(Vec::new()),
+                ScannedNode::Symlink(target) => Node::Symlink(target),
+            };
+            DEnt {
+                name: &s.name,
+                inode: Rc::new(RefCell::new(Inode { node, meta: s.meta })),
+            }
+        })
+        .collect();
+    DTree { children }
+}
@@ -1,5 +1,46 @@
 //! Directory Tree Simulator: Provides a directory tree structure and an operating system stub
 //! structure to interact with it.
+//!
+//! With the `serde` feature, trees, entries and operating system state can be serialized and
+//! deserialized. Names and symbolic link targets are borrowed from the input, so they must be
+//! stored unescaped: in JSON, a name containing `"` or `\` is written with escapes, and
+//! reading it back fails with "expected a borrowed string". Each name for a shared node is
+//! serialized as a separate copy of the node, so hard links do not survive the round trip;
+//! the copies are given a link count of 1 when deserialized. Deserializing checks names as the
+//! rest of the crate does, rejecting empty names, names containing `/`, `.`, `..`, two
+//! entries of the same name in one directory, malformed symbolic link targets, and a working
+//! directory that is not a directory in the tree.
+//! With the `regex` feature, [`Query::name_regex`] matches names by regular expression.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! # use dtree::{DTree, LinkMode, OsState};
+//! let mut dt = DTree::new();
+//! dt.mkdir_all(&["a", "b"]).unwrap();
+//! dt.create("a/f").unwrap();
+//! let json = serde_json::to_string(&dt).unwrap();
+//! let copy: DTree = serde_json::from_str(&json).unwrap();
+//! assert_eq!(copy.paths(), dt.paths());
+//!
+//! dt.link("a/f", "g", LinkMode::FilesOnly).unwrap();
+//! let json = serde_json::to_string(&dt).unwrap();
+//! let copy: DTree = serde_json::from_str(&json).unwrap();
+//! assert_eq!(copy.stat("g").unwrap().meta.nlink, 1);
+//!
+//! let bad = json.replace("\"g\"", "\"a\"");
+//! assert!(serde_json::from_str::<DTree>(&bad).is_err());
+//!
+//! let mut s = OsState::new();
+//! s.dtree = dt;
+//! let json = serde_json::to_string(&s).unwrap();
+//! assert!(serde_json::from_str::<OsState>(&json).is_ok());
+//! let bad = json.replace("\"cwd\":[]", "\"cwd\":[\"nope\"]");
+//! assert!(serde_json::from_str::<OsState>(&bad).is_err());
+//! let bad = json.replace("\"cwd\":[]", "\"cwd\":[\"a\",\"f\"]");
+//! assert!(serde_json::from_str::<OsState>(&bad).is_err());
+//! # }
+//! ```
 
 // Bart Massey 2021
 
@@ -7,8 +48,51 @@
 // https://github.com/rust-lang/rust-clippy/issues/6546
 #![allow(clippy::result_unit_err)]
 
+mod clock;
+mod collect;
+mod diff;
+mod edit;
+mod glob;
+mod import;
+mod materialize;
+mod merge;
+mod meta;
+mod parse;
+mod path;
+mod query;
+mod render;
+mod resolve;
+mod snapshot;
+mod visit;
+mod walk;
+
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use collect::PathListError;
+pub use diff::{Aspect, Change, ChangeKind, Diff};
+pub use edit::{Edit, EditScript};
+pub use glob::{Glob, GlobError, Globbed};
+pub use import::{FsTree, ImportOptions, SymlinkMode};
+pub use materialize::{Materialize, MaterializeError, OnError};
+pub use merge::{Conflict, ConflictKind, KeepChanged, Merge, MergeStrategy, Resolution, Side};
+pub use meta::{Metadata, NodeKind, Stat, DIR_MODE, FILE_MODE, SYMLINK_MODE};
+pub use parse::{ParseError, ParseErrorKind};
+pub use path::DPath;
+pub use query::{Found, Query};
+pub use render::Render;
+pub use resolve::SYMLOOP_MAX;
+pub use snapshot::{SnapshotError, SNAPSHOT_VERSION};
+pub use visit::{VisitFlow, Visitor};
+pub use walk::{Order, Walk, WalkEntry};
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::SystemTime;
+
 use thiserror::Error;
 
+use clock::tree_now;
+
 /// Errors during directory interaction.
 #[derive(Error, Debug)]
 pub enum DirError<'a> {
@@ -16,44 +100,443 @@ pub enum DirError<'a> {
     /// to make path separators easier.
     #[error("{0}: slash in name is invalid")]
     SlashInName(&'a str),
-    /// Only one subdirectory of a given name can exist in any directory.
-    #[error("{0}: directory exists")]
+    /// Component names must not be empty.
+    #[error("{0:?}: invalid name")]
+    InvalidName(&'a str),
+    /// Only one entry of a given name can exist in any directory.
+    #[error("{0}: already exists")]
     DirExists(&'a str),
     /// Traversal failed due to missing subdirectory.
     #[error("{0}: invalid element in path")]
     InvalidChild(&'a str),
+    /// A path string could not be parsed.
+    #[error("{0:?}: malformed path")]
+    InvalidPath(&'a str),
+    /// A `..` component would leave the directory the path is relative to.
+    #[error("{0}: no parent directory")]
+    NoParent(&'a str),
+    /// Only an empty directory can be removed without removing its contents.
+    #[error("{0}: directory not empty")]
+    NotEmpty(&'a str),
+    /// The directory is the root, or is in use as or above the current working directory.
+    #[error("{0}: directory busy")]
+    Busy(&'a str),
+    /// A directory cannot be moved into itself or one of its descendants.
+    #[error("{0}: cannot move directory into itself")]
+    MoveIntoSelf(&'a str),
+    /// A directory cannot be linked into itself or one of its descendants.
+    #[error("{0}: cannot link directory into itself")]
+    LinkIntoSelf(&'a str),
+    /// Traversal or a directory operation reached a file.
+    #[error("{0}: not a directory")]
+    NotADirectory(&'a str),
+    /// A file operation reached a directory.
+    #[error("{0}: is a directory")]
+    IsADirectory(&'a str),
+    /// Resolving a path followed more than [`SYMLOOP_MAX`] symbolic links, as a cycle of links
+    /// would.
+    #[error("{0}: too many levels of symbolic links")]
+    TooManyLinks(&'a str),
+    /// A symbolic link operation reached something other than a symbolic link.
+    #[error("{0}: not a symbolic link")]
+    NotASymlink(&'a str),
 }
 
 /// Result type for directory errors.
 pub type Result<'a, T> = std::result::Result<T, DirError<'a>>;
 
-/// A directory entry. Component names are stored externally.
+/// What [`DTree::rename`] does when the destination already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameMode {
+    /// Fail with `DirError::DirExists`.
+    NoReplace,
+    /// Replace a destination directory if it is empty, or a destination file with a file;
+    /// otherwise fail.
+    ReplaceEmpty,
+}
+
+/// Which kinds of entry [`DTree::link`] may give another name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMode {
+    /// Link files and symbolic links only; fail with `DirError::IsADirectory` for a directory.
+    FilesOnly,
+    /// Link directories as well, so that a subtree appears under more than one name. A
+    /// directory may not be linked into itself.
+    WithDirs,
+}
+
+/// What a directory entry refers to.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Node<'a> {
+    /// A subdirectory.
+    Dir(#[cfg_attr(feature = "serde", serde(borrow))] DTree<'a>),
+    /// A regular file and its contents.
+    File(Vec<u8>),
+    /// A symbolic link and the path it refers to.
+    Symlink(&'a str),
+}
+
+/// A node and its metadata, shared by every entry that is a name for it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inode<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub node: Node<'a>,
+    pub meta: Metadata,
+}
+
+/// A directory entry: a name for a reference-counted node. Component names are stored
+/// externally. Cloning an entry copies its node, sharing nothing with the original; use
+/// [`DEnt::share`] for another name for the same node.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DEnt<'a> {
     pub name: &'a str,
-    pub subdir: DTree<'a>,
+    #[cfg_attr(feature = "serde", serde(borrow, deserialize_with = "fresh_inode"))]
+    pub inode: Rc<RefCell<Inode<'a>>>,
 }
 
 /// A directory tree.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DTree<'a> {
     pub children: Vec<DEnt<'a>>,
 }
 
-/// Operating system state: the directory tree and the current working directory.
-#[derive(Debug, Clone, Default)]
+/// Deserialize a node that has just the one name, whatever link count was serialized,
+/// checking the target of a symbolic link.
+#[cfg(feature = "serde")]
+fn fresh_inode<'de: 'a, 'a, D>(d: D) -> std::result::Result<Rc<RefCell<Inode<'a>>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let inode: Rc<RefCell<Inode<'a>>> = serde::Deserialize::deserialize(d)?;
+    if let Node::Symlink(target) = inode.borrow().node {
+        DPath::parse(target).map_err(serde::de::Error::custom)?;
+    }
+    inode.borrow_mut().meta.nlink = 1;
+    Ok(inode)
+}
+
+/// Deserialize a directory, checking that its entries have valid, distinct names.
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for DTree<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "DTree")]
+        struct Raw<'a> {
+            #[serde(borrow)]
+            children: Vec<DEnt<'a>>,
+        }
+        let raw = Raw::deserialize(d)?;
+        let dt = DTree {
+            children: raw.children,
+        };
+        dt.check_children().map_err(serde::de::Error::custom)?;
+        Ok(dt)
+    }
+}
+
+/// Operating system state: the directory tree, the current working directory, and the clock
+/// used to stamp entries. The clock is not serialized; deserialized state uses the
+/// [`SystemClock`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OsState<'a> {
     pub dtree: DTree<'a>,
     pub cwd: Vec<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub clock: Rc<dyn Clock>,
+}
+
+/// Deserialize operating system state, checking that the working directory is a directory in
+/// the tree.
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for OsState<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "OsState")]
+        struct Raw<'a> {
+            #[serde(borrow)]
+            dtree: DTree<'a>,
+            #[serde(borrow)]
+            cwd: Vec<&'a str>,
+        }
+        let raw = Raw::deserialize(d)?;
+        let check = || -> Result<'a, ()> {
+            for c in &raw.cwd {
+                check_name(c)?;
+            }
+            raw.dtree.in_dir(&raw.cwd, |_| ())
+        };
+        check().map_err(serde::de::Error::custom)?;
+        let mut s = OsState::new();
+        s.dtree = raw.dtree;
+        s.cwd = raw.cwd;
+        Ok(s)
+    }
+}
+
+impl<'a> Default for OsState<'a> {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
 }
 
 impl<'a> DEnt<'a> {
-    pub fn new(name: &'a str) -> Result<Self> {
-        Ok(DEnt { 
-            name, 
-            subdir:DTree::new(),    
+    /// Create a new entry with the given `name` and an empty subdirectory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DEnt;
+    /// assert!(DEnt::new("a").unwrap().is_dir());
+    /// assert!(DEnt::new("..").is_err());
+    /// assert!(DEnt::new_file(".").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::SlashInName` if `name` contains `/`.
+    /// * `DirError::InvalidName` if `name` is empty.
+    /// * `DirError::DirExists` if `name` is `.` or `..`.
+    pub fn new(name: &'a str) -> Result<'a, Self> {
+        Self::with_node(name, Node::Dir(DTree::new()), tree_now())
+    }
+
+    /// Create a new entry with the given `name` and an empty regular file.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::SlashInName` if `name` contains `/`.
+    /// * `DirError::InvalidName` if `name` is empty.
+    /// * `DirError::DirExists` if `name` is `.` or `..`.
+    pub fn new_file(name: &'a str) -> Result<'a, Self> {
+        Self::with_node(name, Node::File(Vec::new()), tree_now())
+    }
+
+    /// Create a new entry with the given `name` and a symbolic link to `target`.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::SlashInName` if `name` contains `/`.
+    /// * `DirError::InvalidName` if `name` is empty.
+    /// * `DirError::DirExists` if `name` is `.` or `..`.
+    /// * `DirError::InvalidPath` if `target` is malformed.
+    pub fn new_symlink(name: &'a str, target: &'a str) -> Result<'a, Self> {
+        DPath::parse(target)?;
+        Self::with_node(name, Node::Symlink(target), tree_now())
+    }
+
+    /// Create a new entry with the given `name` referring to `node`, created at `now` with the
+    /// default permissions for its kind. `.` and `..` are taken by every directory already.
+    fn with_node(name: &'a str, node: Node<'a>, now: SystemTime) -> Result<'a, Self> {
+        check_name(name)?;
+        if name == "." || name == ".." {
+            return Err(DirError::DirExists(name));
+        }
+        let mode = match node {
+            Node::Dir(_) => DIR_MODE,
+            Node::File(_) => FILE_MODE,
+            Node::Symlink(_) => SYMLINK_MODE,
+        };
+        Ok(DEnt {
+            name,
+            inode: Rc::new(RefCell::new(Inode {
+                node,
+                meta: Metadata::new(mode, now),
+            })),
+        })
+    }
+
+    /// The node this entry refers to.
+    pub fn node(&self) -> Ref<'_, Node<'a>> {
+        Ref::map(self.inode.borrow(), |inode| &inode.node)
+    }
+
+    /// The metadata of the node this entry refers to.
+    pub fn meta(&self) -> Metadata {
+        self.inode.borrow().meta
+    }
+
+    /// True if this entry and `other` are names for the same node.
+    pub fn same_node(&self, other: &DEnt<'a>) -> bool {
+        Rc::ptr_eq(&self.inode, &other.inode)
+    }
+
+    /// What kind of node this entry refers to.
+    pub fn kind(&self) -> NodeKind {
+        match self.inode.borrow().node {
+            Node::Dir(_) => NodeKind::Dir,
+            Node::File(_) => NodeKind::File,
+            Node::Symlink(_) => NodeKind::Symlink,
+        }
+    }
+
+    /// Information about this entry: its kind, length and metadata.
+    pub fn stat(&self) -> Stat {
+        let inode = self.inode.borrow();
+        let mut meta = inode.meta;
+        let len = match &inode.node {
+            Node::Dir(dt) => {
+                let subdirs = dt.children.iter().filter(|d| d.is_dir()).count();
+                meta.nlink += 1 + subdirs as u64;
+                dt.children.len()
+            }
+            Node::File(contents) => contents.len(),
+            Node::Symlink(target) => target.len(),
+        };
+        Stat {
+            kind: self.kind(),
+            len: len as u64,
+            meta,
+        }
+    }
+
+    /// True if this entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        matches!(self.inode.borrow().node, Node::Dir(_))
+    }
+
+    /// True if this entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        matches!(self.inode.borrow().node, Node::File(_))
+    }
+
+    /// True if this entry is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        matches!(self.inode.borrow().node, Node::Symlink(_))
+    }
+
+    /// The target of the symbolic link this entry refers to, if it is one.
+    pub fn link_target(&self) -> Option<&'a str> {
+        match self.inode.borrow().node {
+            Node::Symlink(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Another entry for the node this entry refers to, with the same name. Unlike
+    /// [`DTree::link`], this does not add to the node's link count: if the new entry is put
+    /// in a tree as another name for the node, the caller is to keep the count right.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DEnt;
+    /// let f = DEnt::new_file("f").unwrap();
+    /// let mut g = f.share();
+    /// g.name = "g";
+    /// assert!(f.same_node(&g));
+    /// assert!(!f.same_node(&f.clone()));
+    /// ```
+    pub fn share(&self) -> Self {
+        DEnt {
+            name: self.name,
+            inode: Rc::clone(&self.inode),
+        }
+    }
+
+    /// The subdirectory this entry refers to, if it is a directory.
+    pub fn subdir(&self) -> Option<Ref<'_, DTree<'a>>> {
+        Ref::filter_map(self.inode.borrow(), |inode| match &inode.node {
+            Node::Dir(dt) => Some(dt),
+            _ => None,
+        })
+        .ok()
+    }
+
+    /// The subdirectory this entry refers to mutably, if it is a directory.
+    pub fn subdir_mut(&mut self) -> Option<RefMut<'_, DTree<'a>>> {
+        RefMut::filter_map(self.inode.borrow_mut(), |inode| match &mut inode.node {
+            Node::Dir(dt) => Some(dt),
+            _ => None,
         })
+        .ok()
+    }
+}
+
+/// Remove the name `ent` for its node at time `now`. When the last name for a directory is
+/// removed, so are the names of everything in it.
+fn release(ent: DEnt<'_>, now: SystemTime) {
+    let mut inode = ent.inode.borrow_mut();
+    inode.meta.nlink = inode.meta.nlink.saturating_sub(1);
+    inode.meta.changed(now);
+    if inode.meta.nlink == 0 {
+        if let Node::Dir(dt) = &mut inode.node {
+            for d in dt.children.drain(..) {
+                release(d, now);
+            }
+        }
+    }
+}
+
+impl<'a> Clone for DEnt<'a> {
+    /// Copy the entry and everything below it. The copy shares no nodes with this entry;
+    /// nodes with several names below it are shared within the copy, with link counts giving
+    /// the names in the copy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, LinkMode};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.link("a/f", "g", LinkMode::FilesOnly).unwrap();
+    /// let mut copy = DTree::new();
+    /// copy.children.push(dt.children[0].clone());
+    /// copy.write("a/f", b"copy").unwrap();
+    /// assert!(dt.read("g").unwrap().is_empty());
+    /// assert_eq!(copy.stat("a/f").unwrap().meta.nlink, 1);
+    /// ```
+    fn clone(&self) -> Self {
+        let dt = DTree {
+            children: vec![self.share()],
+        };
+        let copy = dt.copy(&mut HashMap::new());
+        copy.recount_links();
+        let DTree { mut children } = copy;
+        children.pop().expect("the copy has the entry")
+    }
+}
+
+impl<'a> Clone for DTree<'a> {
+    /// Copy the tree. Entries that are names for the same node in this tree are names for the
+    /// same node in the copy, but the copy shares no nodes with this tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, LinkMode};
+    /// let mut dt = DTree::new();
+    /// dt.create("f").unwrap();
+    /// dt.link("f", "g", LinkMode::FilesOnly).unwrap();
+    /// let mut copy = dt.clone();
+    /// copy.write("f", b"copy").unwrap();
+    /// assert_eq!(copy.read("g").unwrap(), b"copy");
+    /// assert!(dt.read("g").unwrap().is_empty());
+    /// ```
+    fn clone(&self) -> Self {
+        self.copy(&mut HashMap::new())
+    }
+}
+
+/// Check that `name` is usable as a component name.
+///
+/// # Errors
+///
+/// * `DirError::SlashInName` if `name` contains `/`.
+/// * `DirError::InvalidName` if `name` is empty.
+fn check_name(name: &str) -> Result<'_, ()> {
+    if name.contains('/') {
+        return Err(DirError::SlashInName(name));
     }
+    if name.is_empty() {
+        return Err(DirError::InvalidName(name));
+    }
+    Ok(())
 }
 
 impl<'a> DTree<'a> {
@@ -76,54 +559,187 @@ impl<'a> DTree<'a> {
     /// # Errors
     ///
     /// * `DirError::SlashInName` if `name` contains `/`.
-    /// * `DirError::DirExists` if `name` already exists.
-    pub fn mkdir(&mut self, name: &'a str) -> Result<()> {
-        if name.contains("/"){ return Err(DirError::SlashInName("{0}: slash in name is invalid"));}
-        let d: DEnt<'a> = DEnt::new(name).unwrap();
-        let mut found: bool = false;
-        for n in &self.children{
-           if n.name.eq(name){found = true;}
+    /// * `DirError::InvalidName` if `name` is empty.
+    /// * `DirError::DirExists` if `name` already exists, or is `.` or `..`.
+    pub fn mkdir(&mut self, name: &'a str) -> Result<'a, ()> {
+        self.make_dir(name, tree_now())
+    }
+
+    /// Copy this directory, using the node copies already made in `copies`, keyed by the
+    /// node copied, and adding to them.
+    fn copy(
+        &self,
+        copies: &mut HashMap<*const RefCell<Inode<'a>>, Rc<RefCell<Inode<'a>>>>,
+    ) -> Self {
+        let mut children = Vec::with_capacity(self.children.len());
+        for d in &self.children {
+            let key = Rc::as_ptr(&d.inode);
+            let inode = match copies.get(&key) {
+                Some(inode) => Rc::clone(inode),
+                None => {
+                    let original = d.inode.borrow();
+                    let node = match &original.node {
+                        Node::Dir(dt) => Node::Dir(dt.copy(copies)),
+                        node => node.clone(),
+                    };
+                    let inode = Rc::new(RefCell::new(Inode {
+                        node,
+                        meta: original.meta,
+                    }));
+                    copies.insert(key, Rc::clone(&inode));
+                    inode
+                }
+            };
+            children.push(DEnt {
+                name: d.name,
+                inode,
+            });
         }
-        match found {
-            true => Err(DirError::DirExists("{0}: directory exists")),
-            false => {
-                self.children.push(d);
-                Ok(())
-            },
+        DTree { children }
+    }
+
+    /// Set the link count of every node below this directory to the number of names it has
+    /// here, as for a copy of part of a tree. Symbolic links are not followed.
+    fn recount_links(&self) {
+        fn count<'a>(
+            dt: &DTree<'a>,
+            counts: &mut HashMap<*const RefCell<Inode<'a>>, (Rc<RefCell<Inode<'a>>>, u64)>,
+        ) {
+            for d in &dt.children {
+                let (_, n) = counts
+                    .entry(Rc::as_ptr(&d.inode))
+                    .or_insert_with(|| (Rc::clone(&d.inode), 0));
+                *n += 1;
+                if *n == 1 {
+                    if let Some(sub) = d.subdir() {
+                        count(&sub, counts);
+                    }
+                }
+            }
+        }
+        let mut counts = HashMap::new();
+        count(self, &mut counts);
+        for (inode, n) in counts.into_values() {
+            inode.borrow_mut().meta.nlink = n;
         }
     }
 
-    /// Traverse to the subdirectory given by `path` and then call `f` to visit the subdirectory.
+    /// Make a subdirectory with the given name in this directory, created at `now`.
+    fn make_dir(&mut self, name: &'a str, now: SystemTime) -> Result<'a, ()> {
+        let d = DEnt::with_node(name, Node::Dir(DTree::new()), now)?;
+        if self.child(name).is_some() {
+            return Err(DirError::DirExists(name));
+        }
+        self.children.push(d);
+        Ok(())
+    }
+
+    /// Make the subdirectory given by `path`, first making any of its ancestors that are
+    /// missing. `.`, `..` and symbolic links are followed as they are reached, so `a/b/../c`
+    /// makes `a/b` as well as `a/c`, but `..` may not leave this directory. It is not an error
+    /// for the subdirectory to exist already. Every name, and every step of the walk, is
+    /// checked before anything is made.
     ///
     /// # Examples
     ///
     /// ```
     /// # use dtree::DTree;
     /// let mut dt = DTree::new();
-    /// dt.mkdir("test").unwrap();
-    /// let paths = dt.with_subdir(&["test"], |dt| dt.paths()).unwrap();
-    /// assert_eq!(&paths, &["/"]);
+    /// dt.mkdir_all(&["a", "b", "c"]).unwrap();
+    /// dt.mkdir_all(&["a", "b", "c"]).unwrap();
+    /// dt.mkdir_all(&["a", "d", "..", "e"]).unwrap();
+    /// assert!(dt.mkdir_all(&["a", "f", "g/h"]).is_err());
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/c/", "/a/d/", "/a/e/"]);
+    /// assert!(dt.mkdir_all(&["..", "x"]).is_err());
+    /// assert!(dt.mkdir_all(&["x", "..", "..", "y"]).is_err());
+    /// assert_eq!(dt.children.len(), 1);
     /// ```
     ///
     /// # Errors
     ///
-    /// * `DirError::InvalidChild` if `path` is invalid.
-    pub fn with_subdir<'b, F, R>(&'b self, path: &[&'a str], f: F) -> Result<R>
-    where
-        F: FnOnce(&'b DTree<'a>) -> R,
-    {
-        for p in path{
-            for d in &self.children{
-                if d.name == p.to_string(){
-                    return Ok(f(&d.subdir));
+    /// * `DirError::SlashInName` if a component of `path` contains `/`.
+    /// * `DirError::InvalidName` if a component of `path` is empty.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    pub fn mkdir_all(&mut self, path: &[&'a str]) -> Result<'a, ()> {
+        self.make_all(Vec::new(), path, false, tree_now())
+    }
+
+    /// Make each directory along `path` in turn, starting from the directory given by the
+    /// components `dir`. A `..` at the top either stays there, if `clamp` is set, or fails.
+    /// The whole walk is checked before any directory is made. New directories are created
+    /// at `now`.
+    fn make_all(
+        &mut self,
+        dir: Vec<&'a str>,
+        path: &[&'a str],
+        clamp: bool,
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        for &name in path {
+            if name != "." && name != ".." {
+                check_name(name)?;
+            }
+        }
+        self.make_along(dir.clone(), path, clamp, None)?;
+        self.make_along(dir, path, clamp, Some(now))
+    }
+
+    /// Walk `path` from the directory given by the components `dir`, as for
+    /// [`DTree::make_all`], making each missing directory at `now` if it is given, and
+    /// otherwise only checking that the walk can be made.
+    fn make_along(
+        &mut self,
+        mut dir: Vec<&'a str>,
+        path: &[&'a str],
+        clamp: bool,
+        now: Option<SystemTime>,
+    ) -> Result<'a, ()> {
+        // The number of directories at the end of `dir` that would be made, when checking.
+        let mut missing: usize = 0;
+        for &name in path {
+            match name {
+                "." => (),
+                ".." => {
+                    if dir.pop().is_none() && !clamp {
+                        return Err(DirError::NoParent(name));
+                    }
+                    missing = missing.saturating_sub(1);
+                }
+                _ => {
+                    let kind = if missing > 0 {
+                        None
+                    } else {
+                        self.in_dir(&dir, |dt| dt.child(name).map(DEnt::kind))?
+                    };
+                    match kind {
+                        Some(NodeKind::Dir) => dir.push(name),
+                        Some(NodeKind::File) => return Err(DirError::NotADirectory(name)),
+                        Some(NodeKind::Symlink) => {
+                            dir = self.lookup(dir, &[name], true, clamp)?;
+                            self.in_dir(&dir, |_| ())?;
+                        }
+                        None => {
+                            match now {
+                                Some(now) => {
+                                    self.in_dir_mut(&dir, |dt| dt.make_dir(name, now))??;
+                                    self.touch(&dir, now);
+                                }
+                                None => missing += 1,
+                            }
+                            dir.push(name);
+                        }
+                    }
                 }
             }
         }
-        Err(DirError::InvalidChild("Invalid Child"))
+        Ok(())
     }
 
-    /// Traverse to the subdirectory given by `path` and then call `f` to visit the subdirectory
-    /// mutably.
+    /// Remove the empty subdirectory given by `path`. `.` and `..` are resolved as for
+    /// [`DTree::with_subdir`].
     ///
     /// # Examples
     ///
@@ -131,34 +747,29 @@ impl<'a> DTree<'a> {
     /// # use dtree::DTree;
     /// let mut dt = DTree::new();
     /// dt.mkdir("a").unwrap();
-    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("b").unwrap()).unwrap();
-    /// assert_eq!(&dt.paths(), &["/a/b/"]);
+    /// dt.with_path_mut("a", |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// assert!(dt.rmdir("a").is_err());
+    /// dt.rmdir("a/b").unwrap();
+    /// dt.rmdir("/a/").unwrap();
+    /// assert!(dt.children.is_empty());
     /// ```
     ///
     /// # Errors
     ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
     /// * `DirError::InvalidChild` if `path` is invalid.
-    pub fn with_subdir_mut<'b, F, R>(&'b mut self, path: &[&'a str], f: F) -> Result<R>
-    where
-        F: FnOnce(&'b mut DTree<'a>) -> R,
-    {
-        for p in path{
-            self.find_child(p);
-            return Ok(f(self));
-        }
-        Err(DirError::InvalidChild("Invalid child in with sub dir"))
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if `path` or one of its components is a file.
+    /// * `DirError::NotEmpty` if the subdirectory has children.
+    /// * `DirError::Busy` if `path` refers to this directory itself.
+    pub fn rmdir(&mut self, path: &'a str) -> Result<'a, ()> {
+        let components = self.resolve(path, false)?;
+        self.unlink(path, &components, false, tree_now())
     }
 
-    fn find_child<'b>(&'b self, p: &&str) -> &'b DTree<'a>{
-        for d in &self.children{
-            if p.to_string() == d.name{
-                return &d.subdir;
-            }
-        }
-        panic!("Invalid child")
-    }
-    /// Produce a list of the paths to each reachable leaf, in no particular order.  Path
-    /// components are prefixed by `/`.
+    /// Remove the entry given by `path` together with everything below it. A symbolic link
+    /// is removed itself, not what it refers to. If the entry has other names, only this name
+    /// is removed.
     ///
     /// # Examples
     ///
@@ -166,119 +777,1515 @@ impl<'a> DTree<'a> {
     /// # use dtree::DTree;
     /// let mut dt = DTree::new();
     /// dt.mkdir("a").unwrap();
-    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("b").unwrap()).unwrap();
-    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("c").unwrap()).unwrap();
-    /// let mut paths = dt.paths();
-    /// paths.sort();
-    /// assert_eq!(&paths, &["/a/b/", "/a/c/"]);
+    /// dt.mkdir("c").unwrap();
+    /// dt.with_path_mut("a", |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// dt.remove_all("a").unwrap();
+    /// assert_eq!(&dt.paths(), &["/c/"]);
     /// ```
-  
-    pub fn paths(&self) -> Vec<String> {
-        let mut retpaths: Vec<String> = Vec::new();
-        if self.children.is_empty(){
-            retpaths.push("/".to_string())
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::Busy` if `path` refers to this directory itself.
+    pub fn remove_all(&mut self, path: &'a str) -> Result<'a, ()> {
+        let components = self.resolve(path, false)?;
+        self.unlink(path, &components, true, tree_now())
+    }
+
+    /// Move the entry given by `from` so that it is instead given by `to`, renaming it to the
+    /// last component of `to`. `mode` says what to do if `to` already exists. Moving an entry
+    /// onto itself does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, RenameMode};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.mkdir("b").unwrap();
+    /// dt.with_path_mut("a", |dt| dt.mkdir("c").unwrap()).unwrap();
+    /// assert!(dt.rename("a", "a/c/d", RenameMode::NoReplace).is_err());
+    /// assert!(dt.rename("a/c", "b", RenameMode::NoReplace).is_err());
+    /// dt.rename("a/c", "b", RenameMode::ReplaceEmpty).unwrap();
+    /// dt.rename("/b", "/a/d", RenameMode::NoReplace).unwrap();
+    /// assert_eq!(&dt.paths(), &["/a/d/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `from` or `to` is malformed.
+    /// * `DirError::InvalidChild` if `from` or the parent of `to` is invalid.
+    /// * `DirError::NoParent` if `from` or `to` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `from` or `to` is a file.
+    /// * `DirError::Busy` if `from` or `to` refers to this directory itself.
+    /// * `DirError::MoveIntoSelf` if `to` is below `from`.
+    /// * `DirError::DirExists` if `to` exists and `mode` is `RenameMode::NoReplace`.
+    /// * `DirError::NotEmpty` if `to` is a directory that is not empty and `mode` is
+    ///   `RenameMode::ReplaceEmpty`.
+    /// * `DirError::NotADirectory` or `DirError::IsADirectory` if `to` exists and is not the
+    ///   same kind of entry as `from`, and `mode` is `RenameMode::ReplaceEmpty`.
+    pub fn rename(&mut self, from: &'a str, to: &'a str, mode: RenameMode) -> Result<'a, ()> {
+        let source = self.resolve(from, false)?;
+        let target = self.resolve(to, false)?;
+        self.move_entry(from, &source, to, &target, mode, tree_now())
+    }
+
+    /// Move the entry at the resolved `source` components of `from` to the resolved `target`
+    /// components of `to` at time `now`. All checks are made before the tree is changed.
+    fn move_entry(
+        &mut self,
+        from: &'a str,
+        source: &[&'a str],
+        to: &'a str,
+        target: &[&'a str],
+        mode: RenameMode,
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        if source.is_empty() {
+            return Err(DirError::Busy(from));
         }
-        for n in &self.children {
-            retpaths.push(format!("/{}{}", n.name, n.subdir.path_helper()));
+        let (&name, parent) = target.split_last().ok_or(DirError::Busy(to))?;
+        let is_dir = self.with_entry(from, source, DEnt::is_dir)?;
+        if source == target {
+            return Ok(());
+        }
+        if target.starts_with(source) || is_dir && self.reaches_from(from, source, parent)? {
+            return Err(DirError::MoveIntoSelf(to));
+        }
+        let existing = self.in_dir(parent, |dt| {
+            dt.child(name)
+                .map(|d| d.subdir().map(|dt| dt.children.is_empty()))
+        })?;
+        match (existing, mode) {
+            (None, _) => (),
+            (Some(_), RenameMode::NoReplace) => return Err(DirError::DirExists(to)),
+            (Some(None), RenameMode::ReplaceEmpty) if is_dir => {
+                return Err(DirError::NotADirectory(to))
+            }
+            (Some(Some(_)), RenameMode::ReplaceEmpty) if !is_dir => {
+                return Err(DirError::IsADirectory(to))
+            }
+            (Some(Some(false)), RenameMode::ReplaceEmpty) => return Err(DirError::NotEmpty(to)),
+            (Some(_), RenameMode::ReplaceEmpty) => {
+                self.unlink(to, target, true, now)?;
+            }
         }
-        retpaths
+        let mut ent = self.remove(from, source, true, now)?;
+        ent.name = name;
+        ent.inode.borrow_mut().meta.changed(now);
+        self.in_dir_mut(parent, |dt| dt.children.push(ent))?;
+        self.touch(parent, now);
+        Ok(())
     }
-    
-    fn path_helper(&self) -> String{
-        let mut cwd: String = String::new();
-        if self.children.is_empty(){return "/".to_string();}
-        for z in &self.children{
-            cwd = format!("/{}{}", z.name, z.subdir.path_helper())
-       }
-        cwd
+
+    /// True if the directory given by the canonical components `dir` is the directory at the
+    /// resolved `source` components of `from` or is below it through some name.
+    fn reaches_from(&self, from: &'a str, source: &[&'a str], dir: &[&'a str]) -> Result<'a, bool> {
+        let inode = self.with_entry(from, source, |d| Rc::clone(&d.inode))?;
+        let target = self.in_dir(dir, |dt| dt as *const DTree<'a>)?;
+        let inode = inode.borrow();
+        Ok(matches!(&inode.node, Node::Dir(dt) if dt.reaches(target)))
     }
-}
 
-impl<'a> OsState<'a> {
-    /// Create a new directory tree in the operating system.  Current working directory is the
-    /// root.
-    pub fn new() -> Self {
-        Self::default()
+    /// Give the entry at the resolved `source` components of `from` another name at the
+    /// resolved `target` components of `to` at time `now`.
+    fn link_entry(
+        &mut self,
+        from: &'a str,
+        source: &[&'a str],
+        to: &'a str,
+        target: &[&'a str],
+        mode: LinkMode,
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        let (&name, parent) = target.split_last().ok_or(DirError::DirExists(to))?;
+        let is_dir = self.with_entry(from, source, DEnt::is_dir)?;
+        if is_dir && mode == LinkMode::FilesOnly {
+            return Err(DirError::IsADirectory(from));
+        }
+        if is_dir && self.reaches_from(from, source, parent)? {
+            return Err(DirError::LinkIntoSelf(to));
+        }
+        let inode = self.with_entry(from, source, |d| Rc::clone(&d.inode))?;
+        self.in_dir_mut(parent, |dt| {
+            if dt.child(name).is_some() {
+                return Err(DirError::DirExists(to));
+            }
+            dt.children.push(DEnt {
+                name,
+                inode: Rc::clone(&inode),
+            });
+            Ok(())
+        })??;
+        let mut inode = inode.borrow_mut();
+        inode.meta.nlink += 1;
+        inode.meta.changed(now);
+        drop(inode);
+        self.touch(parent, now);
+        Ok(())
     }
 
-    /// If `path` is empty, change the working directory to the root.  Otherwise change the
-    /// working directory to the subdirectory given by `path` relative to the current working
-    /// directory.  (There is no notion of `.` or `..`: `path` must be a valid sequence of
-    /// component names.)
+    /// Remove the name at the resolved `components` of `path` at time `now`, as with
+    /// [`DTree::remove`], and release its node.
+    fn unlink(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        recursive: bool,
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        let ent = self.remove(path, components, recursive, now)?;
+        release(ent, now);
+        Ok(())
+    }
+
+    /// Detach and return the entry at the resolved `components` of `path` at time `now`.
+    /// Unless `recursive` is set, the entry must be an empty directory.
+    fn remove(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        recursive: bool,
+        now: SystemTime,
+    ) -> Result<'a, DEnt<'a>> {
+        let (&name, parent) = components.split_last().ok_or(DirError::Busy(path))?;
+        let ent = self.in_dir_mut(parent, |dt| {
+            let i = dt
+                .children
+                .iter()
+                .position(|d| d.name == name)
+                .ok_or(DirError::InvalidChild(name))?;
+            if !recursive {
+                let empty = dt.children[i].subdir().map(|dt| dt.children.is_empty());
+                match empty {
+                    None => return Err(DirError::NotADirectory(path)),
+                    Some(false) => return Err(DirError::NotEmpty(path)),
+                    Some(true) => (),
+                }
+            }
+            Ok(dt.children.remove(i))
+        })??;
+        self.touch(parent, now);
+        Ok(ent)
+    }
+
+    /// Traverse to the subdirectory given by `path` and then call `f` to visit the subdirectory.
+    /// Components `.` and `..` refer to the current and parent directory, but `..` may not
+    /// leave this directory. Symbolic links are followed, with absolute targets taken from
+    /// this directory.
+    ///
+    /// Subdirectories are reached through shared nodes, borrowed only while `f` runs, so the
+    /// value `f` returns cannot borrow from the subdirectory; copy out what is needed instead.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use dtree::OsState;
-    /// let mut s = OsState::new();
-    /// s.mkdir("a").unwrap();
-    /// s.chdir(&["a"]).unwrap();
-    /// s.mkdir("b").unwrap();
-    /// s.chdir(&["b"]).unwrap();
-    /// s.mkdir("c").unwrap();
-    /// s.chdir(&[]).unwrap();
-    /// assert_eq!(&s.paths().unwrap(), &["/a/b/c/"]);
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("test").unwrap();
+    /// let paths = dt.with_subdir(&["test"], |dt| dt.paths()).unwrap();
+    /// assert_eq!(&paths, &["/"]);
+    /// let paths = dt.with_subdir(&["test", ".", ".."], |dt| dt.paths()).unwrap();
+    /// assert_eq!(&paths, &["/test/"]);
+    /// assert!(dt.with_subdir(&["test", "..", ".."], |_| ()).is_err());
     /// ```
     ///
     /// # Errors
     ///
-    /// * `DirError::InvalidChild` if the new working directory is invalid. On error, the original
-    /// working directory will be retained.
-    pub fn chdir(&mut self, path: &[&'a str]) -> Result<()> {
-        let mut x: DTree<'a> = DTree::new();
-        for p in path{
-            for child in &self.dtree.children{
-                if p.to_string() == child.name{
-                    x = child.subdir.clone();
-                }
-            }
-        }
-        self.dtree = x.clone();
-        Ok(())
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn with_subdir<F, R>(&self, path: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&DTree<'a>) -> R,
+    {
+        let dir = self.lookup(Vec::new(), path, true, false)?;
+        self.in_dir(&dir, f)
     }
 
-    /// Make a new subdirectory with the given `name` in the working directory.
+    /// Traverse to the subdirectory given by `path` without following symbolic links, and then
+    /// call `f` to visit the subdirectory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.symlink("a", "l").unwrap();
+    /// assert!(dt.with_subdir(&["l"], |_| ()).is_ok());
+    /// assert!(dt.with_subdir_nofollow(&["l"], |_| ()).is_err());
+    /// ```
     ///
     /// # Errors
     ///
-    /// * `DirError::SlashInName` if `name` contains `/`.
-    /// * `DirError::InvalidChild` if the current working directory is invalid.
-    /// * `DirError::DirExists` if `name` already exists.
-    pub fn mkdir(&mut self, name: &'a str) -> Result<()> {
-        if name.contains("/"){return Err(DirError::SlashInName("Slash in name"))}
-        else{}
-        let d: DEnt<'a> = DEnt::new(name).unwrap();
-        let mut found: bool = false;
-
-        for n in &self.dtree.children{
-            if n.name.eq(name){found=true;}
-        }
-        match found{
-            true => Err(DirError::DirExists("Directory exists")),
-            false => {
-                self.dtree.children.push(d);
-                Ok(())
-            },
-        }
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file or symbolic link.
+    pub fn with_subdir_nofollow<F, R>(&self, path: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&DTree<'a>) -> R,
+    {
+        let dir = path::normalize(path)?;
+        self.in_dir(&dir, f)
     }
 
-    /// Produce a list of the paths from the working directory to each reachable leaf, in no
-    /// particular order.  Path components are separated by `/`.
+    /// Traverse to the subdirectory given by `path` and then call `f` to visit the subdirectory
+    /// mutably. As with [`DTree::with_subdir`], the value `f` returns cannot borrow from the
+    /// subdirectory.
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// * `DirError::InvalidChild` if the current working directory is invalid.
-    pub fn paths(&self) -> Result<Vec<String>> {
-        let mut retpaths: Vec<String> = Vec::new();
-        if self.dtree.children.is_empty(){
-            retpaths.push("/".to_string())
-        }
-        for n in &self.dtree.children{
-            retpaths.push(format!("/{}{}", n.name, n.subdir.path_helper()));
-        }
-        match retpaths.is_empty(){
-            true => Ok(retpaths),
-            _ => Err(DirError::InvalidChild("Invalid child in paths")),
-        }
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// assert_eq!(&dt.paths(), &["/a/b/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn with_subdir_mut<F, R>(&mut self, path: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&mut DTree<'a>) -> R,
+    {
+        let dir = self.lookup(Vec::new(), path, true, false)?;
+        self.in_dir_mut(&dir, f)
+    }
+
+    /// Traverse to the subdirectory given by `path` without following symbolic links, and then
+    /// call `f` to visit the subdirectory mutably.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::with_subdir_nofollow`].
+    pub fn with_subdir_mut_nofollow<F, R>(&mut self, path: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&mut DTree<'a>) -> R,
+    {
+        let dir = path::normalize(path)?;
+        self.in_dir_mut(&dir, f)
+    }
+
+    /// Parse `path` and then call `f` to visit the subdirectory it names, as with
+    /// [`DTree::with_subdir`]. Absolute and relative paths are both taken relative to this
+    /// directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// let paths = dt.with_path("/a//b/", |dt| dt.paths()).unwrap();
+    /// assert_eq!(&paths, &["/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn with_path<F, R>(&self, path: &'a str, f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&DTree<'a>) -> R,
+    {
+        let path = DPath::parse(path)?;
+        self.with_subdir(path.components(), f)
+    }
+
+    /// Parse `path` and then call `f` to visit the subdirectory it names mutably, as with
+    /// [`DTree::with_subdir_mut`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.with_path_mut("a/", |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// assert_eq!(&dt.paths(), &["/a/b/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn with_path_mut<F, R>(&mut self, path: &'a str, f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&mut DTree<'a>) -> R,
+    {
+        let path = DPath::parse(path)?;
+        self.with_subdir_mut(path.components(), f)
+    }
+
+    /// Give the entry given by `from` another name, given by `to`. Both names then refer to
+    /// the same node, so changes made through either are seen through both, and the node's
+    /// link count goes up by one. `mode` says whether directories may be linked. A symbolic
+    /// link in the last component of `from` is linked itself, not what it refers to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, LinkMode};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.link("a/f", "g", LinkMode::FilesOnly).unwrap();
+    /// dt.write("g", b"shared").unwrap();
+    /// assert_eq!(dt.read("a/f").unwrap(), b"shared");
+    /// assert_eq!(dt.stat("g").unwrap().meta.nlink, 2);
+    ///
+    /// assert!(dt.link("a", "b", LinkMode::FilesOnly).is_err());
+    /// dt.link("a", "b", LinkMode::WithDirs).unwrap();
+    /// assert!(dt.link("a", "b/c", LinkMode::WithDirs).is_err());
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/f", "/b/f", "/g"]);
+    ///
+    /// dt.remove_all("a").unwrap();
+    /// assert_eq!(dt.stat("g").unwrap().meta.nlink, 2);
+    /// dt.remove_all("b").unwrap();
+    /// assert_eq!(dt.stat("g").unwrap().meta.nlink, 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `from` or `to` is malformed.
+    /// * `DirError::InvalidChild` if `from` or the parent of `to` is invalid.
+    /// * `DirError::NoParent` if `from` or `to` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `from` or `to` is a file.
+    /// * `DirError::IsADirectory` if `from` is a directory and `mode` is
+    ///   `LinkMode::FilesOnly`.
+    /// * `DirError::LinkIntoSelf` if `from` is a directory and `to` is below it.
+    /// * `DirError::DirExists` if `to` already exists.
+    pub fn link(&mut self, from: &'a str, to: &'a str, mode: LinkMode) -> Result<'a, ()> {
+        let source = self.resolve(from, false)?;
+        let target = self.resolve(to, false)?;
+        self.link_entry(from, &source, to, &target, mode, tree_now())
+    }
+
+    /// Make a new empty regular file given by `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.create("a/f").unwrap();
+    /// assert!(dt.create("a/f").is_err());
+    /// assert!(dt.mkdir_all(&["a", "f", "g"]).is_err());
+    /// assert_eq!(&dt.paths(), &["/a/f"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if the parent of `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::DirExists` if `path` already exists.
+    pub fn create(&mut self, path: &'a str) -> Result<'a, ()> {
+        let components = self.resolve(path, false)?;
+        self.create_file(path, &components, tree_now())
+    }
+
+    /// Replace the contents of the file given by `path` with `data`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.create("f").unwrap();
+    /// dt.write("f", b"hello").unwrap();
+    /// dt.append("f", b", world").unwrap();
+    /// assert_eq!(dt.read("f").unwrap(), b"hello, world");
+    /// dt.truncate("f", 4).unwrap();
+    /// assert_eq!(dt.read("f").unwrap(), b"hell");
+    /// dt.mkdir("d").unwrap();
+    /// assert!(dt.read("d").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::IsADirectory` if `path` is a directory.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn write(&mut self, path: &'a str, data: &[u8]) -> Result<'a, ()> {
+        let components = self.resolve(path, true)?;
+        self.with_file_mut(path, &components, tree_now(), |contents| {
+            contents.clear();
+            contents.extend_from_slice(data);
+        })
+    }
+
+    /// Add `data` to the end of the file given by `path`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::write`].
+    pub fn append(&mut self, path: &'a str, data: &[u8]) -> Result<'a, ()> {
+        let components = self.resolve(path, true)?;
+        self.with_file_mut(path, &components, tree_now(), |contents| {
+            contents.extend_from_slice(data)
+        })
+    }
+
+    /// Return a copy of the contents of the file given by `path`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::write`].
+    pub fn read(&self, path: &'a str) -> Result<'a, Vec<u8>> {
+        let components = self.resolve(path, true)?;
+        self.with_file(path, &components, |contents| contents.to_vec())
+    }
+
+    /// Set the length of the file given by `path` to `len`, discarding contents past `len` or
+    /// extending the file with zero bytes.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::write`].
+    pub fn truncate(&mut self, path: &'a str, len: usize) -> Result<'a, ()> {
+        let components = self.resolve(path, true)?;
+        self.with_file_mut(path, &components, tree_now(), |contents| {
+            contents.resize(len, 0)
+        })
+    }
+
+    /// Make a new empty regular file at the resolved `components` of `path`, created at `now`.
+    fn create_file(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        self.add_entry(path, components, Node::File(Vec::new()), now)
+    }
+
+    /// Add an entry referring to `node` at the resolved `components` of `path`, created at
+    /// `now`.
+    fn add_entry(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        node: Node<'a>,
+        now: SystemTime,
+    ) -> Result<'a, ()> {
+        let (&name, parent) = components.split_last().ok_or(DirError::DirExists(path))?;
+        self.in_dir_mut(parent, |dt| {
+            if dt.child(name).is_some() {
+                return Err(DirError::DirExists(path));
+            }
+            dt.children.push(DEnt::with_node(name, node, now)?);
+            Ok(())
+        })??;
+        self.touch(parent, now);
+        Ok(())
+    }
+
+    /// Call `f` on the contents of the file at the resolved `components` of `path`.
+    fn with_file<F, R>(&self, path: &'a str, components: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&Vec<u8>) -> R,
+    {
+        if components.is_empty() {
+            return Err(DirError::IsADirectory(path));
+        }
+        self.with_entry(path, components, |d| match &*d.node() {
+            Node::File(contents) => Ok(f(contents)),
+            Node::Dir(_) => Err(DirError::IsADirectory(path)),
+            Node::Symlink(_) => Err(DirError::TooManyLinks(path)),
+        })?
+    }
+
+    /// Call `f` on the contents of the file at the resolved `components` of `path` mutably,
+    /// recording that the file was modified at `now`.
+    fn with_file_mut<F, R>(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        now: SystemTime,
+        f: F,
+    ) -> Result<'a, R>
+    where
+        F: FnOnce(&mut Vec<u8>) -> R,
+    {
+        if components.is_empty() {
+            return Err(DirError::IsADirectory(path));
+        }
+        self.with_entry(path, components, |d| {
+            let mut inode = d.inode.borrow_mut();
+            let Inode { node, meta } = &mut *inode;
+            match node {
+                Node::File(contents) => {
+                    meta.modified(now);
+                    Ok(f(contents))
+                }
+                Node::Dir(_) => Err(DirError::IsADirectory(path)),
+                Node::Symlink(_) => Err(DirError::TooManyLinks(path)),
+            }
+        })?
+    }
+
+    /// Call `f` on the entry at the resolved `components` of `path`.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the path is invalid or is the root, which has no entry.
+    fn with_entry<F, R>(&self, path: &'a str, components: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&DEnt<'a>) -> R,
+    {
+        let (&name, parent) = components
+            .split_last()
+            .ok_or(DirError::InvalidChild(path))?;
+        self.in_dir(parent, |dt| dt.child(name).map(f))?
+            .ok_or(DirError::InvalidChild(name))
+    }
+
+    /// Record that the directory at the resolved `components` had its contents modified at
+    /// `now`. The root has no entry to record this in.
+    fn touch(&self, components: &[&'a str], now: SystemTime) {
+        if let Some((&name, parent)) = components.split_last() {
+            let _ = self.in_dir(parent, |dt| {
+                if let Some(d) = dt.child(name) {
+                    d.inode.borrow_mut().meta.modified(now);
+                }
+            });
+        }
+    }
+
+    /// Return information about the entry given by `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, NodeKind};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.write("a/f", b"abc").unwrap();
+    /// let st = dt.stat("a").unwrap();
+    /// assert_eq!(st.kind, NodeKind::Dir);
+    /// assert_eq!(st.len, 2);
+    /// assert_eq!(st.meta.nlink, 3);
+    /// assert!(st.meta.mtime >= dt.stat("a/b").unwrap().meta.mtime);
+    /// let st = dt.stat("a/f").unwrap();
+    /// assert_eq!((st.kind, st.len, st.meta.mode), (NodeKind::File, 3, 0o644));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid or is the root, which has no entry.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    pub fn stat(&self, path: &'a str) -> Result<'a, Stat> {
+        let components = self.resolve(path, true)?;
+        self.with_entry(path, &components, DEnt::stat)
+    }
+
+    /// Return information about the entry given by `path`, as with [`DTree::stat`], but
+    /// describing a symbolic link itself rather than what it refers to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, NodeKind};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.symlink("/a", "l").unwrap();
+    /// assert_eq!(dt.stat("l").unwrap().kind, NodeKind::Dir);
+    /// assert_eq!(dt.lstat("l").unwrap().kind, NodeKind::Symlink);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::stat`].
+    pub fn lstat(&self, path: &'a str) -> Result<'a, Stat> {
+        let components = self.resolve(path, false)?;
+        self.with_entry(path, &components, DEnt::stat)
+    }
+
+    /// Make a new symbolic link given by `path` that refers to `target`. The target need not
+    /// exist. When the link is followed, an absolute target is taken from the top of the tree
+    /// and a relative one from the directory containing the link.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.symlink("..", "a/b/up").unwrap();
+    /// assert_eq!(dt.readlink("a/b/up").unwrap(), "..");
+    /// dt.with_path_mut("a/b/up/b/up/b", |dt| dt.mkdir("c").unwrap()).unwrap();
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/c/", "/a/b/up"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `target` or `path` is malformed.
+    /// * `DirError::InvalidChild` if the parent of `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::DirExists` if `path` already exists.
+    pub fn symlink(&mut self, target: &'a str, path: &'a str) -> Result<'a, ()> {
+        DPath::parse(target)?;
+        let components = self.resolve(path, false)?;
+        self.add_entry(path, &components, Node::Symlink(target), tree_now())
+    }
+
+    /// Return the target of the symbolic link given by `path`.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NoParent` if `path` has a `..` with no parent to refer to.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::NotASymlink` if `path` is not a symbolic link.
+    pub fn readlink(&self, path: &'a str) -> Result<'a, &'a str> {
+        let components = self.resolve(path, false)?;
+        self.with_entry(path, &components, DEnt::link_target)?
+            .ok_or(DirError::NotASymlink(path))
+    }
+
+    /// Parse `path` and resolve it from this directory, following a symbolic link in the last
+    /// component if `follow` is set.
+    fn resolve(&self, path: &'a str, follow: bool) -> Result<'a, Vec<&'a str>> {
+        self.lookup(Vec::new(), DPath::parse(path)?.components(), follow, false)
+    }
+
+    /// Set the permission bits of the entry given by `path` to `mode`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.chmod("a", 0o700).unwrap();
+    /// dt.chown("a", 1000, 100).unwrap();
+    /// let meta = dt.stat("a").unwrap().meta;
+    /// assert_eq!((meta.mode, meta.uid, meta.gid), (0o700, 1000, 100));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::stat`].
+    pub fn chmod(&mut self, path: &'a str, mode: u32) -> Result<'a, ()> {
+        let components = self.resolve(path, true)?;
+        self.set_meta(path, &components, tree_now(), |meta| meta.mode = mode)
+    }
+
+    /// Set the owning user and group of the entry given by `path`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::stat`].
+    pub fn chown(&mut self, path: &'a str, uid: u32, gid: u32) -> Result<'a, ()> {
+        let components = self.resolve(path, true)?;
+        self.set_meta(path, &components, tree_now(), |meta| {
+            meta.uid = uid;
+            meta.gid = gid;
+        })
+    }
+
+    /// Call `f` to change the metadata of the entry at the resolved `components` of `path`,
+    /// recording that it was changed at `now`.
+    fn set_meta<F>(
+        &mut self,
+        path: &'a str,
+        components: &[&'a str],
+        now: SystemTime,
+        f: F,
+    ) -> Result<'a, ()>
+    where
+        F: FnOnce(&mut Metadata),
+    {
+        self.with_entry(path, components, |d| {
+            let meta = &mut d.inode.borrow_mut().meta;
+            f(meta);
+            meta.changed(now);
+        })
+    }
+
+    /// Find the entry in this directory with the given `name`.
+    fn child(&self, name: &str) -> Option<&DEnt<'a>> {
+        self.children.iter().find(|d| d.name == name)
+    }
+
+    /// Produce a list of the paths to each reachable leaf, in no particular order.  Path
+    /// components are prefixed by `/`. Paths to directories end in `/`; paths to files do not.
+    /// Symbolic links to directories are followed, except where that would lead back to a
+    /// directory already being listed; those links, and links to anything else, are leaves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir("a").unwrap();
+    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("b").unwrap()).unwrap();
+    /// dt.with_subdir_mut(&["a"], |dt| dt.mkdir("c").unwrap()).unwrap();
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/", "/a/c/"]);
+    /// ```
+    pub fn paths(&self) -> Vec<String> {
+        self.leaf_paths(&[], true, false)
+            .expect("the top of the tree is a directory")
+    }
+
+    /// Produce a list of the paths to each reachable leaf, as with [`DTree::paths`], without
+    /// following symbolic links: every link is a leaf.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.symlink("a", "l").unwrap();
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/", "/l/b/"]);
+    /// let mut paths = dt.paths_nofollow();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/", "/l"]);
+    /// ```
+    pub fn paths_nofollow(&self) -> Vec<String> {
+        self.leaf_paths(&[], false, false)
+            .expect("the top of the tree is a directory")
+    }
+
+    /// Start a lazy walk over the entries below this directory. See [`Walk`] for the options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, Order};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b", "c"]).unwrap();
+    /// dt.mkdir_all(&["a", "d"]).unwrap();
+    /// dt.create("e").unwrap();
+    /// let leaves: Vec<_> = dt.walk().map(|w| w.path.join("/")).collect();
+    /// assert_eq!(&leaves, &["a/b/c", "a/d", "e"]);
+    ///
+    /// let post: Vec<_> = dt
+    ///     .walk()
+    ///     .order(Order::PostOrder)
+    ///     .include_dirs(true)
+    ///     .map(|w| w.entry.name)
+    ///     .collect();
+    /// assert_eq!(&post, &["c", "b", "d", "a", "e"]);
+    ///
+    /// let bfs: Vec<_> = dt
+    ///     .walk()
+    ///     .order(Order::BreadthFirst)
+    ///     .include_dirs(true)
+    ///     .min_depth(2)
+    ///     .max_depth(2)
+    ///     .map(|w| (w.depth, w.entry.name))
+    ///     .collect();
+    /// assert_eq!(&bfs, &[(2, "b"), (2, "d")]);
+    /// ```
+    pub fn walk(&self) -> Walk<'_, 'a> {
+        Walk::new(self)
+    }
+
+    /// Produce a list of the paths to each reachable leaf below the directory given by the
+    /// canonical components `dir`, following symbolic links if `follow` is set. `clamp` is as
+    /// for [`DTree::lookup`].
+    fn leaf_paths(&self, dir: &[&'a str], follow: bool, clamp: bool) -> Result<'a, Vec<String>> {
+        let mut lister = Lister {
+            root: self,
+            follow,
+            clamp,
+            listing: vec![dir.to_vec()],
+            paths: Vec::new(),
+        };
+        self.in_dir(dir, |dt| lister.list(dt, dir, "/"))?;
+        Ok(lister.paths)
+    }
+}
+
+/// State for listing the leaf paths of a tree.
+struct Lister<'t, 'a> {
+    /// The top of the tree, from which absolute link targets are taken.
+    root: &'t DTree<'a>,
+    /// Whether symbolic links to directories are followed.
+    follow: bool,
+    /// Whether `..` in link targets stays at the top of the tree.
+    clamp: bool,
+    /// The canonical components of each directory being listed.
+    listing: Vec<Vec<&'a str>>,
+    /// The leaf paths found so far.
+    paths: Vec<String>,
+}
+
+impl<'t, 'a> Lister<'t, 'a> {
+    /// Add the paths of each leaf below `dt`, which has canonical components `dir` and is
+    /// named by `prefix`. A directory reached through more than one name is listed under
+    /// each of them.
+    fn list(&mut self, dt: &DTree<'a>, dir: &[&'a str], prefix: &str) {
+        if dt.children.is_empty() {
+            self.paths.push(prefix.to_string());
+            return;
+        }
+        for d in &dt.children {
+            let mut path = dir.to_vec();
+            path.push(d.name);
+            let name = format!("{}{}", prefix, d.name);
+            let listed = if let Some(subdir) = d.subdir() {
+                self.descend(&subdir, &path, &name);
+                true
+            } else if let Some(target) = self.follow(path) {
+                let root = self.root;
+                root.in_dir(&target, |dt| self.descend(dt, &target, &name))
+                    .is_ok()
+            } else {
+                false
+            };
+            if !listed {
+                self.paths.push(name);
+            }
+        }
+    }
+
+    /// List the directory `dt`, which has canonical components `dir` and is named by `name`.
+    fn descend(&mut self, dt: &DTree<'a>, dir: &[&'a str], name: &str) {
+        self.listing.push(dir.to_vec());
+        self.list(dt, dir, &format!("{}/", name));
+        self.listing.pop();
+    }
+
+    /// Resolve the symbolic link with canonical components `link`, unless it is not being
+    /// followed or leads back to a directory already being listed.
+    fn follow(&self, link: Vec<&'a str>) -> Option<Vec<&'a str>> {
+        if !self.follow {
+            return None;
+        }
+        let (&name, parent) = link.split_last()?;
+        let target = self
+            .root
+            .lookup(parent.to_vec(), &[name], true, self.clamp)
+            .ok()?;
+        if self.listing.contains(&target) {
+            return None;
+        }
+        Some(target)
+    }
+}
+
+impl<'a> OsState<'a> {
+    /// Create a new directory tree in the operating system.  Current working directory is the
+    /// root.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new directory tree in the operating system whose entries are stamped using
+    /// `clock`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{FakeClock, OsState};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let clock = FakeClock::new(UNIX_EPOCH);
+    /// let mut s = OsState::with_clock(clock.clone());
+    /// s.mkdir("a").unwrap();
+    /// clock.advance(Duration::from_secs(10));
+    /// s.mkdir_all(&["a", "b"]).unwrap();
+    /// let a = s.stat("a").unwrap().meta;
+    /// assert_eq!(a.atime, UNIX_EPOCH);
+    /// assert_eq!(a.mtime, UNIX_EPOCH + Duration::from_secs(10));
+    /// ```
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        OsState {
+            dtree: DTree::new(),
+            cwd: Vec::new(),
+            clock: Rc::new(clock),
+        }
+    }
+
+    /// If `path` is empty, change the working directory to the root.  Otherwise change the
+    /// working directory to the subdirectory given by `path` relative to the current working
+    /// directory.  The component `.` refers to the current directory and `..` to its parent;
+    /// `..` at the root refers to the root.  Symbolic links are followed, and the new working
+    /// directory is recorded without them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir(&["a"]).unwrap();
+    /// s.mkdir("b").unwrap();
+    /// s.chdir(&["b"]).unwrap();
+    /// s.mkdir("c").unwrap();
+    /// s.chdir(&[]).unwrap();
+    /// assert_eq!(&s.paths().unwrap(), &["/a/b/c/"]);
+    /// s.chdir(&["a", "b", "..", ".", "..", ".."]).unwrap();
+    /// assert!(s.cwd.is_empty());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the new working directory is invalid. On error, the original
+    ///   working directory will be retained.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn chdir(&mut self, path: &[&'a str]) -> Result<'a, ()> {
+        if path.is_empty() {
+            self.cwd.clear();
+            return Ok(());
+        }
+        let cwd = self.dtree.lookup(self.cwd.clone(), path, true, true)?;
+        self.dtree.in_dir(&cwd, |_| ())?;
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    /// Change the working directory as with [`OsState::chdir`], but without following
+    /// symbolic links.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.symlink("a", "l").unwrap();
+    /// assert!(s.chdir_nofollow(&["l"]).is_err());
+    /// s.chdir(&["l"]).unwrap();
+    /// assert_eq!(&s.cwd, &["a"]);
+    /// s.chdir_nofollow(&[]).unwrap();
+    /// assert!(s.cwd.is_empty());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the new working directory is invalid. On error, the original
+    ///   working directory will be retained.
+    /// * `DirError::NotADirectory` if a component of `path` is a file or symbolic link.
+    pub fn chdir_nofollow(&mut self, path: &[&'a str]) -> Result<'a, ()> {
+        if path.is_empty() {
+            self.cwd.clear();
+            return Ok(());
+        }
+        let cwd = path::resolve(&self.cwd, path);
+        self.dtree.in_dir(&cwd, |_| ())?;
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    /// Parse `path` and change the working directory to the subdirectory it names. An
+    /// absolute path is taken from the root, a relative one from the current working directory.
+    /// `.` and `..` are resolved as for [`OsState::chdir`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a/").unwrap();
+    /// s.mkdir("b").unwrap();
+    /// s.chdir_path("/a//b").unwrap();
+    /// assert_eq!(&s.cwd, &["a", "b"]);
+    /// s.chdir_path("../../a/./b/..").unwrap();
+    /// assert_eq!(&s.cwd, &["a"]);
+    /// s.chdir_path("/").unwrap();
+    /// assert!(s.cwd.is_empty());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if the new working directory is invalid. On error, the original
+    ///   working directory will be retained.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    pub fn chdir_path(&mut self, path: &'a str) -> Result<'a, ()> {
+        let cwd = self.resolve(path, true)?;
+        self.dtree.in_dir(&cwd, |_| ())?;
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    /// Make a new subdirectory with the given `name` in the working directory.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::SlashInName` if `name` contains `/`.
+    /// * `DirError::InvalidName` if `name` is empty.
+    /// * `DirError::InvalidChild` if the current working directory is invalid.
+    /// * `DirError::DirExists` if `name` already exists, or is `.` or `..`.
+    pub fn mkdir(&mut self, name: &'a str) -> Result<'a, ()> {
+        let now = self.clock.now();
+        self.dtree
+            .in_dir_mut(&self.cwd, |dt| dt.make_dir(name, now))??;
+        self.dtree.touch(&self.cwd, now);
+        Ok(())
+    }
+
+    /// Make the subdirectory given by `path` relative to the current working directory, first
+    /// making any of its ancestors that are missing, as with [`DTree::mkdir_all`]. `..` at the
+    /// root refers to the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir(&["a"]).unwrap();
+    /// s.mkdir_all(&["b", "c"]).unwrap();
+    /// s.mkdir_all(&["..", "..", "d"]).unwrap();
+    /// s.chdir(&[]).unwrap();
+    /// let mut paths = s.paths().unwrap();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/c/", "/d/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::SlashInName` if a component of `path` contains `/`.
+    /// * `DirError::InvalidName` if a component of `path` is empty.
+    /// * `DirError::InvalidChild` if the current working directory is invalid.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    pub fn mkdir_all(&mut self, path: &[&'a str]) -> Result<'a, ()> {
+        self.dtree
+            .make_all(self.cwd.clone(), path, true, self.clock.now())
+    }
+
+    /// Remove the empty subdirectory given by `path`, resolved against the current working
+    /// directory. The working directory and its ancestors cannot be removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.mkdir("b").unwrap();
+    /// assert!(s.rmdir("..").is_err());
+    /// s.rmdir("b").unwrap();
+    /// assert!(s.rmdir(".").is_err());
+    /// s.chdir_path("..").unwrap();
+    /// s.rmdir("a").unwrap();
+    /// assert_eq!(&s.paths().unwrap(), &["/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NotADirectory` if `path` or one of its components is a file.
+    /// * `DirError::NotEmpty` if the subdirectory has children.
+    /// * `DirError::Busy` if `path` refers to the working directory or one of its ancestors.
+    pub fn rmdir(&mut self, path: &'a str) -> Result<'a, ()> {
+        self.remove(path, false, false)
+    }
+
+    /// Remove the entry given by `path`, resolved against the current working directory,
+    /// together with everything below it. The working directory and its ancestors
+    /// cannot be removed: see [`OsState::remove_all_forced`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.mkdir("b").unwrap();
+    /// s.chdir_path("b").unwrap();
+    /// assert!(s.remove_all("/a").is_err());
+    /// s.chdir_path("/").unwrap();
+    /// s.remove_all("a").unwrap();
+    /// assert_eq!(&s.paths().unwrap(), &["/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::Busy` if `path` refers to the working directory or one of its ancestors.
+    pub fn remove_all(&mut self, path: &'a str) -> Result<'a, ()> {
+        self.remove(path, true, false)
+    }
+
+    /// Remove the subdirectory given by `path` together with everything below it, even if it
+    /// is or contains the current working directory. The working directory is left as it was,
+    /// and subsequent operations relative to it will fail until it is changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.remove_all_forced(".").unwrap();
+    /// assert!(s.paths().is_err());
+    /// s.chdir_path("/").unwrap();
+    /// assert_eq!(&s.paths().unwrap(), &["/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::Busy` if `path` refers to the root.
+    pub fn remove_all_forced(&mut self, path: &'a str) -> Result<'a, ()> {
+        self.remove(path, true, true)
+    }
+
+    /// Remove the subdirectory given by `path`, refusing to remove the working directory or
+    /// its ancestors unless `force` is set.
+    fn remove(&mut self, path: &'a str, recursive: bool, force: bool) -> Result<'a, ()> {
+        let target = self.resolve(path, false)?;
+        if !force && self.cwd.starts_with(&target) {
+            return Err(DirError::Busy(path));
+        }
+        self.dtree
+            .unlink(path, &target, recursive, self.clock.now())
+    }
+
+    /// Move the entry given by `from` so that it is instead given by `to`, as with
+    /// [`DTree::rename`], with both paths resolved against the current working directory. If
+    /// the working directory is moved, it follows the move.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{OsState, RenameMode};
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.mkdir("b").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.rename("../b", "c", RenameMode::NoReplace).unwrap();
+    /// s.rename(".", "/d", RenameMode::NoReplace).unwrap();
+    /// assert_eq!(&s.cwd, &["d"]);
+    /// assert_eq!(&s.paths().unwrap(), &["/c/"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `from` or `to` is malformed.
+    /// * `DirError::InvalidChild` if `from` or the parent of `to` is invalid.
+    /// * `DirError::Busy` if `from` or `to` is the root, or `to` is the working directory or
+    ///   one of its ancestors.
+    /// * `DirError::MoveIntoSelf` if `to` is below `from`.
+    /// * `DirError::DirExists` if `to` exists and `mode` is `RenameMode::NoReplace`.
+    /// * `DirError::NotEmpty` if `to` is a directory that is not empty and `mode` is
+    ///   `RenameMode::ReplaceEmpty`.
+    /// * `DirError::NotADirectory` or `DirError::IsADirectory` if `to` exists and is not the
+    ///   same kind of entry as `from`, and `mode` is `RenameMode::ReplaceEmpty`.
+    pub fn rename(&mut self, from: &'a str, to: &'a str, mode: RenameMode) -> Result<'a, ()> {
+        let source = self.resolve(from, false)?;
+        let target = self.resolve(to, false)?;
+        if source != target && self.cwd.starts_with(&target) {
+            return Err(DirError::Busy(to));
+        }
+        self.dtree
+            .move_entry(from, &source, to, &target, mode, self.clock.now())?;
+        if self.cwd.starts_with(&source) {
+            let rest = self.cwd.split_off(source.len());
+            self.cwd = target;
+            self.cwd.extend(rest);
+        }
+        Ok(())
+    }
+
+    /// Give the entry given by `from` another name, given by `to`, as with [`DTree::link`],
+    /// with both paths resolved against the current working directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{LinkMode, OsState};
+    /// let mut s = OsState::new();
+    /// s.mkdir_all(&["a", "b"]).unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.link("b", "/c", LinkMode::WithDirs).unwrap();
+    /// s.chdir_path("/c").unwrap();
+    /// s.mkdir("d").unwrap();
+    /// s.chdir_path("/").unwrap();
+    /// let mut paths = s.paths().unwrap();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/b/d/", "/c/d/"]);
+    /// assert_eq!(s.stat("c").unwrap().meta.nlink, 4);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::link`].
+    pub fn link(&mut self, from: &'a str, to: &'a str, mode: LinkMode) -> Result<'a, ()> {
+        let source = self.resolve(from, false)?;
+        let target = self.resolve(to, false)?;
+        self.dtree
+            .link_entry(from, &source, to, &target, mode, self.clock.now())
+    }
+
+    /// Make a new empty regular file given by `path`, resolved against the current working
+    /// directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// s.create("f").unwrap();
+    /// s.write("/a/f", b"abc").unwrap();
+    /// s.append("./f", b"def").unwrap();
+    /// s.truncate("../a/f", 5).unwrap();
+    /// assert_eq!(s.read("f").unwrap(), b"abcde");
+    /// assert!(s.chdir_path("f").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if the parent of `path` is invalid.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::DirExists` if `path` already exists.
+    pub fn create(&mut self, path: &'a str) -> Result<'a, ()> {
+        let target = self.resolve(path, false)?;
+        self.dtree.create_file(path, &target, self.clock.now())
+    }
+
+    /// Replace the contents of the file given by `path`, resolved against the current working
+    /// directory, with `data`.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::IsADirectory` if `path` is a directory.
+    /// * `DirError::TooManyLinks` if resolving `path` follows too many symbolic links.
+    pub fn write(&mut self, path: &'a str, data: &[u8]) -> Result<'a, ()> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .with_file_mut(path, &target, self.clock.now(), |contents| {
+                contents.clear();
+                contents.extend_from_slice(data);
+            })
+    }
+
+    /// Add `data` to the end of the file given by `path`, resolved against the current working
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::write`].
+    pub fn append(&mut self, path: &'a str, data: &[u8]) -> Result<'a, ()> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .with_file_mut(path, &target, self.clock.now(), |contents| {
+                contents.extend_from_slice(data)
+            })
+    }
+
+    /// Return a copy of the contents of the file given by `path`, resolved against the current
+    /// working directory.
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::write`].
+    pub fn read(&self, path: &'a str) -> Result<'a, Vec<u8>> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .with_file(path, &target, |contents| contents.to_vec())
+    }
+
+    /// Set the length of the file given by `path`, resolved against the current working
+    /// directory, to `len`, as with [`DTree::truncate`].
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::write`].
+    pub fn truncate(&mut self, path: &'a str, len: usize) -> Result<'a, ()> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .with_file_mut(path, &target, self.clock.now(), |contents| {
+                contents.resize(len, 0)
+            })
+    }
+
+    /// Return information about the entry given by `path`, resolved against the current
+    /// working directory, as with [`DTree::stat`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{NodeKind, OsState};
+    /// let mut s = OsState::new();
+    /// s.mkdir("a").unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// let before = s.stat(".").unwrap().meta.mtime;
+    /// s.create("f").unwrap();
+    /// assert!(s.stat("/a").unwrap().meta.mtime >= before);
+    /// assert_eq!(s.stat("f").unwrap().kind, NodeKind::File);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid or is the root, which has no entry.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    pub fn stat(&self, path: &'a str) -> Result<'a, Stat> {
+        let target = self.resolve(path, true)?;
+        self.dtree.with_entry(path, &target, DEnt::stat)
+    }
+
+    /// Set the permission bits of the entry given by `path`, resolved against the current
+    /// working directory, to `mode`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::stat`].
+    pub fn chmod(&mut self, path: &'a str, mode: u32) -> Result<'a, ()> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .set_meta(path, &target, self.clock.now(), |meta| meta.mode = mode)
+    }
+
+    /// Set the owning user and group of the entry given by `path`, resolved against the
+    /// current working directory.
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::stat`].
+    pub fn chown(&mut self, path: &'a str, uid: u32, gid: u32) -> Result<'a, ()> {
+        let target = self.resolve(path, true)?;
+        self.dtree
+            .set_meta(path, &target, self.clock.now(), |meta| {
+                meta.uid = uid;
+                meta.gid = gid;
+            })
+    }
+
+    /// Return information about the entry given by `path`, resolved against the current
+    /// working directory, describing a symbolic link itself rather than what it refers to.
+    ///
+    /// # Errors
+    ///
+    /// As for [`OsState::stat`].
+    pub fn lstat(&self, path: &'a str) -> Result<'a, Stat> {
+        let target = self.resolve(path, false)?;
+        self.dtree.with_entry(path, &target, DEnt::stat)
+    }
+
+    /// Make a new symbolic link given by `path`, resolved against the current working
+    /// directory, that refers to `target`, as with [`DTree::symlink`]. A relative target is
+    /// taken from the directory containing the link, not the working directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir_all(&["a", "b"]).unwrap();
+    /// s.chdir_path("a/b").unwrap();
+    /// s.symlink("..", "up").unwrap();
+    /// s.symlink("loop", "loop").unwrap();
+    /// assert!(s.stat("loop").is_err());
+    /// s.chdir_path("up/b/up/../..").unwrap();
+    /// assert!(s.cwd.is_empty());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `target` or `path` is malformed.
+    /// * `DirError::InvalidChild` if the parent of `path` is invalid.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::DirExists` if `path` already exists.
+    pub fn symlink(&mut self, target: &'a str, path: &'a str) -> Result<'a, ()> {
+        DPath::parse(target)?;
+        let link = self.resolve(path, false)?;
+        self.dtree
+            .add_entry(path, &link, Node::Symlink(target), self.clock.now())
+    }
+
+    /// Return the target of the symbolic link given by `path`, resolved against the current
+    /// working directory.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if `path` is invalid.
+    /// * `DirError::NotADirectory` if a component of `path` is a file.
+    /// * `DirError::NotASymlink` if `path` is not a symbolic link.
+    pub fn readlink(&self, path: &'a str) -> Result<'a, &'a str> {
+        let link = self.resolve(path, false)?;
+        self.dtree
+            .with_entry(path, &link, DEnt::link_target)?
+            .ok_or(DirError::NotASymlink(path))
+    }
+
+    /// Parse `path` and resolve it against the current working directory, following a
+    /// symbolic link in the last component if `follow` is set.
+    fn resolve(&self, path: &'a str, follow: bool) -> Result<'a, Vec<&'a str>> {
+        let path = DPath::parse(path)?;
+        let base = if path.is_absolute() {
+            Vec::new()
+        } else {
+            self.cwd.clone()
+        };
+        self.dtree.lookup(base, path.components(), follow, true)
+    }
+
+    /// Produce a list of the paths from the working directory to each reachable leaf, in no
+    /// particular order.  Path components are separated by `/`.  Symbolic links are followed
+    /// as for [`DTree::paths`].
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the current working directory is invalid.
+    pub fn paths(&self) -> Result<'a, Vec<String>> {
+        self.dtree.leaf_paths(&self.cwd, true, true)
+    }
+
+    /// Produce a list of the paths from the working directory to each reachable leaf without
+    /// following symbolic links, as for [`DTree::paths_nofollow`].
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if the current working directory is invalid.
+    pub fn paths_nofollow(&self) -> Result<'a, Vec<String>> {
+        self.dtree.leaf_paths(&self.cwd, false, true)
     }
 }
@@ -0,0 +1,209 @@
+//! Materializing a tree onto a real directory with `std::fs`, the reverse of [`FsTree`].
+//!
+//! [`FsTree`]: crate::FsTree
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{DTree, Order, OsState, Result};
+
+/// What [`Materialize::apply`] does when a directory cannot be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    /// Make nothing more.
+    Stop,
+    /// Go on with the remaining directories. Those below a directory that could not be made
+    /// are not attempted.
+    Continue,
+}
+
+/// The directories [`Materialize::apply`] could not make, those it did make, and those it
+/// did not attempt.
+#[derive(Debug)]
+pub struct MaterializeError {
+    /// The directories made, in the order they were made.
+    pub made: Vec<PathBuf>,
+    /// Each directory that could not be made, in the order attempted, and why.
+    pub failed: Vec<(PathBuf, io::Error)>,
+    /// The directories not attempted, in the planned order: those below a directory that
+    /// could not be made, and, with [`OnError::Stop`], every directory planned after the
+    /// first failure.
+    pub skipped: Vec<PathBuf>,
+}
+
+impl fmt::Display for MaterializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "making directories: {} failed, {} made, {} skipped",
+            self.failed.len(),
+            self.made.len(),
+            self.skipped.len(),
+        )?;
+        if let Some((dir, e)) = self.failed.first() {
+            write!(f, ": {}: {}", dir.display(), e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for MaterializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.failed
+            .first()
+            .map(|(_, e)| e as &(dyn Error + 'static))
+    }
+}
+
+/// A plan to make the directory structure of a tree below a real directory, as returned by
+/// [`DTree::materialize`] and [`OsState::materialize`]. Only directories are made: files and
+/// symbolic links are left out, as are directories below symbolic links. A directory with
+/// several names is made under each. By default [`Materialize::apply`] stops at the first
+/// error.
+#[derive(Debug, Clone)]
+pub struct Materialize {
+    /// The directories to make, each after the directory containing it.
+    dirs: Vec<PathBuf>,
+    on_error: OnError,
+}
+
+impl Materialize {
+    /// Plan to make the directories of `dt` below `root`.
+    fn new(dt: &DTree<'_>, root: &Path) -> Self {
+        let dirs = dt
+            .walk()
+            .order(Order::PreOrder)
+            .include_dirs(true)
+            .filter(|w| w.entry.is_dir())
+            .map(|w| w.path.iter().fold(root.to_path_buf(), |p, c| p.join(c)))
+            .collect();
+        Materialize {
+            dirs,
+            on_error: OnError::Stop,
+        }
+    }
+
+    /// Handle errors in [`Materialize::apply`] as `policy` says.
+    pub fn on_error(mut self, policy: OnError) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    /// The directories that [`Materialize::apply`] would make, in order, one `create_dir`
+    /// call each, without touching the filesystem.
+    pub fn plan(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Make the planned directories in order. The real root must exist already. A planned
+    /// directory that exists already as a directory is left as it is. Returns the directories
+    /// made.
+    ///
+    /// # Errors
+    ///
+    /// * A [`MaterializeError`] if any directory could not be made, giving the directories
+    ///   that were made, those that could not be, and those skipped below them, after
+    ///   stopping or going on as [`Materialize::on_error`] says.
+    pub fn apply(&self) -> std::result::Result<Vec<PathBuf>, MaterializeError> {
+        let mut made = Vec::new();
+        let mut failed: Vec<(PathBuf, io::Error)> = Vec::new();
+        let mut skipped = Vec::new();
+        for dir in &self.dirs {
+            let stopped = self.on_error == OnError::Stop && !failed.is_empty();
+            if stopped || failed.iter().any(|(f, _)| dir.starts_with(f)) {
+                skipped.push(dir.clone());
+                continue;
+            }
+            match fs::create_dir(dir) {
+                Ok(()) => made.push(dir.clone()),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => (),
+                Err(e) => failed.push((dir.clone(), e)),
+            }
+        }
+        if failed.is_empty() {
+            Ok(made)
+        } else {
+            Err(MaterializeError {
+                made,
+                failed,
+                skipped,
+            })
+        }
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Plan to make the directory structure of this directory below the real directory
+    /// `root`. See [`Materialize`] for what is made and the options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, FsTree, ImportOptions, OnError};
+    /// # use std::fs;
+    /// let root = std::env::temp_dir().join(format!("dtree-materialize-{}", std::process::id()));
+    /// fs::create_dir_all(root.join("a")).unwrap();
+    ///
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.mkdir("c").unwrap();
+    /// let m = dt.materialize(&root).on_error(OnError::Continue);
+    /// let planned: Vec<_> = m.plan().iter().map(|p| p.strip_prefix(&root).unwrap()).collect();
+    /// assert_eq!(planned, ["a", "a/b", "c"].iter().map(std::path::Path::new).collect::<Vec<_>>());
+    ///
+    /// assert_eq!(m.apply().unwrap(), [root.join("a/b"), root.join("c")]);
+    /// let scan = FsTree::scan(&root, &ImportOptions::new()).unwrap();
+    /// assert_eq!(&scan.tree().paths(), &["/a/b/", "/c/"]);
+    ///
+    /// fs::remove_dir(root.join("c")).unwrap();
+    /// fs::remove_dir(root.join("a/b")).unwrap();
+    /// fs::remove_dir(root.join("a")).unwrap();
+    /// fs::write(root.join("a"), b"").unwrap();
+    /// let err = m.apply().unwrap_err();
+    /// assert_eq!(err.made, [root.join("c")]);
+    /// assert_eq!(err.failed[0].0, root.join("a"));
+    /// assert_eq!(err.skipped, [root.join("a/b")]);
+    ///
+    /// let err = m.on_error(OnError::Stop).apply().unwrap_err();
+    /// assert!(err.made.is_empty());
+    /// assert_eq!(err.skipped, [root.join("a/b"), root.join("c")]);
+    /// assert!(err.to_string().starts_with("making directories: 1 failed, 0 made, 2 skipped: "));
+    /// # fs::remove_dir_all(&root).unwrap();
+    /// ```
+    pub fn materialize(&self, root: impl AsRef<Path>) -> Materialize {
+        Materialize::new(self, root.as_ref())
+    }
+}
+
+impl<'a> OsState<'a> {
+    /// Plan to make the directory structure of the directory given by `path`, resolved
+    /// against the current working directory, below the real directory `root`, as with
+    /// [`DTree::materialize`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir_all(&["a", "b", "c"]).unwrap();
+    /// s.chdir_path("a").unwrap();
+    /// let m = s.materialize("b", "/srv").unwrap();
+    /// assert_eq!(m.plan(), [std::path::Path::new("/srv/c")]);
+    /// assert!(s.materialize("/x", "/srv").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is malformed.
+    /// * `DirError::InvalidChild` if a component of `path` does not exist.
+    /// * `DirError::NotADirectory` if `path` or a component of it is a file.
+    pub fn materialize(&self, path: &'a str, root: impl AsRef<Path>) -> Result<'a, Materialize> {
+        let dir = self.resolve(path, true)?;
+        self.dtree
+            .in_dir(&dir, |dt| Materialize::new(dt, root.as_ref()))
+    }
+}
@@ -0,0 +1,381 @@
+//! Three-way merges of trees, with conflicts resolved by pluggable strategies.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+use crate::{DEnt, DTree, Inode, Node};
+
+/// One of the two trees being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The tree merged into.
+    Ours,
+    /// The tree merged from.
+    Theirs,
+}
+
+/// How the two sides of a conflict differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides added different entries where the base had none.
+    BothAdded,
+    /// Both sides changed the entry differently.
+    BothChanged,
+    /// The given side removed the entry, and the other changed it or something below it.
+    Removed(Side),
+}
+
+/// Which version of a conflicting entry to take. A version may be absent, in which case the
+/// entry is left out of the merged tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Take our version.
+    Ours,
+    /// Take their version.
+    Theirs,
+    /// Take the version in the base.
+    Base,
+}
+
+/// An entry that the two sides changed in ways that cannot both be kept. Cloning a conflict
+/// shares the nodes of its entries.
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    /// The component names leading to the entry from the top of the trees.
+    pub path: Vec<&'a str>,
+    /// How the sides differ.
+    pub kind: ConflictKind,
+    /// The entry in the base, if there is one.
+    pub base: Option<DEnt<'a>>,
+    /// Our version of the entry, if there is one.
+    pub ours: Option<DEnt<'a>>,
+    /// Their version of the entry, if there is one.
+    pub theirs: Option<DEnt<'a>>,
+    /// How the conflict was resolved, if the strategy resolved it.
+    pub resolution: Option<Resolution>,
+}
+
+impl<'a> Clone for Conflict<'a> {
+    fn clone(&self) -> Self {
+        Conflict {
+            path: self.path.clone(),
+            kind: self.kind,
+            base: self.base.as_ref().map(DEnt::share),
+            ours: self.ours.as_ref().map(DEnt::share),
+            theirs: self.theirs.as_ref().map(DEnt::share),
+            resolution: self.resolution,
+        }
+    }
+}
+
+/// A way of resolving conflicts in [`DTree::merge`].
+///
+/// Any `FnMut(&Conflict<'a>) -> Option<Resolution>` is a strategy, as is a [`Resolution`],
+/// which resolves every conflict the same way.
+pub trait MergeStrategy<'a> {
+    /// Say how to resolve `conflict`, or return `None` to leave it unresolved.
+    fn resolve(&mut self, conflict: &Conflict<'a>) -> Option<Resolution>;
+}
+
+impl<'a, F: FnMut(&Conflict<'a>) -> Option<Resolution>> MergeStrategy<'a> for F {
+    fn resolve(&mut self, conflict: &Conflict<'a>) -> Option<Resolution> {
+        self(conflict)
+    }
+}
+
+impl<'a> MergeStrategy<'a> for Resolution {
+    fn resolve(&mut self, _conflict: &Conflict<'a>) -> Option<Resolution> {
+        Some(*self)
+    }
+}
+
+/// A strategy that keeps changes over removals: when one side removed an entry that the
+/// other changed, the changed version is taken. Other conflicts are left unresolved.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepChanged;
+
+impl<'a> MergeStrategy<'a> for KeepChanged {
+    fn resolve(&mut self, conflict: &Conflict<'a>) -> Option<Resolution> {
+        match conflict.kind {
+            ConflictKind::Removed(Side::Ours) => Some(Resolution::Theirs),
+            ConflictKind::Removed(Side::Theirs) => Some(Resolution::Ours),
+            _ => None,
+        }
+    }
+}
+
+/// The result of [`DTree::merge`].
+#[derive(Debug, Clone)]
+pub struct Merge<'a> {
+    /// The merged tree. It shares no nodes with the trees merged.
+    pub tree: DTree<'a>,
+    /// Every conflict found, resolved or not, in pre-order with the entries of each
+    /// directory sorted by name.
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+impl<'a> Merge<'a> {
+    /// The conflicts the strategy left unresolved.
+    pub fn unresolved(&self) -> impl Iterator<Item = &Conflict<'a>> {
+        self.conflicts.iter().filter(|c| c.resolution.is_none())
+    }
+}
+
+/// True if `a` and `b` are the same apart from their times and link counts, comparing
+/// everything below directories.
+fn same<'a>(a: &DEnt<'a>, b: &DEnt<'a>) -> bool {
+    if a.same_node(b) {
+        return true;
+    }
+    let (ia, ib) = (a.inode.borrow(), b.inode.borrow());
+    let (ma, mb) = (ia.meta, ib.meta);
+    if (ma.mode, ma.uid, ma.gid) != (mb.mode, mb.uid, mb.gid) {
+        return false;
+    }
+    match (&ia.node, &ib.node) {
+        (Node::Dir(da), Node::Dir(db)) => {
+            da.children.len() == db.children.len()
+                && da
+                    .children
+                    .iter()
+                    .all(|d| db.child(d.name).is_some_and(|e| same(d, e)))
+        }
+        (Node::File(fa), Node::File(fb)) => fa == fb,
+        (Node::Symlink(ta), Node::Symlink(tb)) => ta == tb,
+        _ => false,
+    }
+}
+
+/// True if `a` and `b` are both absent, or the same.
+fn same_option<'a>(a: Option<&DEnt<'a>>, b: Option<&DEnt<'a>>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => same(a, b),
+        _ => false,
+    }
+}
+
+/// A merge in progress.
+struct Merger<'a, 's, S> {
+    strategy: &'s mut S,
+    conflicts: Vec<Conflict<'a>>,
+    /// Copies of the nodes taken so far, by original.
+    copies: HashMap<*const RefCell<Inode<'a>>, Rc<RefCell<Inode<'a>>>>,
+}
+
+impl<'a, 's, S: MergeStrategy<'a>> Merger<'a, 's, S> {
+    /// A copy of `d`, sharing no nodes with it.
+    fn take(&mut self, d: &DEnt<'a>) -> DEnt<'a> {
+        let dt = DTree {
+            children: vec![d.share()],
+        };
+        let mut copy = dt.copy(&mut self.copies);
+        copy.children.pop().expect("the copy has the entry")
+    }
+
+    /// Merge the directories `ours` and `theirs`, at `path`, given the directory `base` they
+    /// both came from, if there is one.
+    fn merge_dirs(
+        &mut self,
+        base: Option<&DTree<'a>>,
+        ours: &DTree<'a>,
+        theirs: &DTree<'a>,
+        path: &mut Vec<&'a str>,
+    ) -> DTree<'a> {
+        let names: BTreeSet<&'a str> = base
+            .into_iter()
+            .flat_map(|dt| &dt.children)
+            .chain(&ours.children)
+            .chain(&theirs.children)
+            .map(|d| d.name)
+            .collect();
+        let mut children = Vec::new();
+        for name in names {
+            let b = base.and_then(|dt| dt.child(name));
+            let (o, t) = (ours.child(name), theirs.child(name));
+            path.push(name);
+            if let Some(d) = self.merge_entries(b, o, t, path) {
+                children.push(d);
+            }
+            path.pop();
+        }
+        DTree { children }
+    }
+
+    /// Record a conflict between the entries `ours` and `theirs`, at `path`, given the entry
+    /// `base` they both came from, and return the strategy's resolution of it.
+    fn conflict(
+        &mut self,
+        base: Option<&DEnt<'a>>,
+        ours: Option<&DEnt<'a>>,
+        theirs: Option<&DEnt<'a>>,
+        path: &[&'a str],
+    ) -> Option<Resolution> {
+        let kind = match (base, ours, theirs) {
+            (None, _, _) => ConflictKind::BothAdded,
+            (Some(_), None, _) => ConflictKind::Removed(Side::Ours),
+            (Some(_), _, None) => ConflictKind::Removed(Side::Theirs),
+            _ => ConflictKind::BothChanged,
+        };
+        let mut conflict = Conflict {
+            path: path.to_vec(),
+            kind,
+            base: base.map(DEnt::share),
+            ours: ours.map(DEnt::share),
+            theirs: theirs.map(DEnt::share),
+            resolution: None,
+        };
+        conflict.resolution = self.strategy.resolve(&conflict);
+        let resolution = conflict.resolution;
+        self.conflicts.push(conflict);
+        resolution
+    }
+
+    /// Merge the entries `ours` and `theirs`, at `path`, given the entry `base` they both
+    /// came from. Returns the merged entry, if there is one.
+    fn merge_entries(
+        &mut self,
+        base: Option<&DEnt<'a>>,
+        ours: Option<&DEnt<'a>>,
+        theirs: Option<&DEnt<'a>>,
+        path: &mut Vec<&'a str>,
+    ) -> Option<DEnt<'a>> {
+        if let (Some(o), Some(t)) = (ours, theirs) {
+            if let (Some(od), Some(td)) = (o.subdir(), t.subdir()) {
+                let (om, tm) = (o.meta(), t.meta());
+                let mut meta = if same_meta(o, t) || base.is_some_and(|b| same_meta(b, t)) {
+                    om
+                } else if base.is_some_and(|b| same_meta(b, o)) {
+                    tm
+                } else {
+                    match self.conflict(base, ours, theirs, path) {
+                        None | Some(Resolution::Ours) => om,
+                        Some(Resolution::Theirs) => tm,
+                        Some(Resolution::Base) => base.map_or(om, DEnt::meta),
+                    }
+                };
+                meta.nlink = 1;
+                let bd = base.and_then(|b| b.subdir());
+                let dt = self.merge_dirs(bd.as_deref(), &od, &td, path);
+                return Some(DEnt {
+                    name: o.name,
+                    inode: Rc::new(RefCell::new(Inode {
+                        node: Node::Dir(dt),
+                        meta,
+                    })),
+                });
+            }
+        }
+        let chosen = if same_option(ours, theirs) || same_option(base, theirs) {
+            ours
+        } else if same_option(base, ours) {
+            theirs
+        } else {
+            match self.conflict(base, ours, theirs, path) {
+                None | Some(Resolution::Ours) => ours,
+                Some(Resolution::Theirs) => theirs,
+                Some(Resolution::Base) => base,
+            }
+        };
+        chosen.map(|d| self.take(d))
+    }
+}
+
+/// True if `a` and `b` have the same permission bits and ownership.
+fn same_meta(a: &DEnt<'_>, b: &DEnt<'_>) -> bool {
+    let (ma, mb) = (a.meta(), b.meta());
+    (ma.mode, ma.uid, ma.gid) == (mb.mode, mb.uid, mb.gid)
+}
+
+impl<'a> DTree<'a> {
+    /// Merge the changes made in `ours` and in `theirs`, both descended from this tree, the
+    /// base. Entries are matched by path, and compared by kind, contents or target,
+    /// permission bits and ownership, but not times. An entry changed on one side only takes
+    /// that side's version; an entry changed the same way on both sides is taken once.
+    /// Directories on both sides are merged entry by entry. Their permission bits and
+    /// ownership are merged like those of other entries; where the sides change them
+    /// differently, the conflict is for the directory's permission bits and ownership alone.
+    /// Anything else is a [`Conflict`], which `strategy` may resolve; an unresolved conflict
+    /// keeps our version. Link counts in the merged tree count the names it has.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{ConflictKind, DTree, KeepChanged, Resolution, Side};
+    /// let mut base = DTree::new();
+    /// base.mkdir("docs").unwrap();
+    /// base.create("f").unwrap();
+    ///
+    /// let mut ours = base.clone();
+    /// ours.remove_all("docs").unwrap();
+    /// ours.mkdir("src").unwrap();
+    /// ours.write("f", b"ours").unwrap();
+    ///
+    /// let mut theirs = base.clone();
+    /// theirs.create("docs/guide").unwrap();
+    /// theirs.mkdir("tests").unwrap();
+    /// theirs.write("f", b"theirs").unwrap();
+    ///
+    /// let m = base.merge(&ours, &theirs, |_: &_| None);
+    /// let kinds: Vec<_> = m.conflicts.iter().map(|c| (c.path.clone(), c.kind)).collect();
+    /// assert_eq!(
+    ///     kinds,
+    ///     [
+    ///         (vec!["docs"], ConflictKind::Removed(Side::Ours)),
+    ///         (vec!["f"], ConflictKind::BothChanged),
+    ///     ],
+    /// );
+    /// assert_eq!(m.unresolved().count(), 2);
+    /// assert_eq!(&m.tree.paths(), &["/f", "/src/", "/tests/"]);
+    /// assert_eq!(m.tree.read("f").unwrap(), b"ours");
+    ///
+    /// let m = base.merge(&ours, &theirs, KeepChanged);
+    /// assert_eq!(&m.tree.paths(), &["/docs/guide", "/f", "/src/", "/tests/"]);
+    /// assert_eq!(m.unresolved().count(), 1);
+    ///
+    /// let m = base.merge(&ours, &theirs, Resolution::Theirs);
+    /// assert_eq!(m.tree.read("f").unwrap(), b"theirs");
+    /// assert_eq!(m.unresolved().count(), 0);
+    /// ```
+    ///
+    /// ```
+    /// # use dtree::{ConflictKind, DTree, LinkMode, Resolution};
+    /// let mut base = DTree::new();
+    /// base.create("f").unwrap();
+    /// base.link("f", "g", LinkMode::FilesOnly).unwrap();
+    /// base.mkdir("d").unwrap();
+    ///
+    /// let mut ours = base.clone();
+    /// ours.chmod("d", 0o700).unwrap();
+    /// let mut theirs = base.clone();
+    /// theirs.remove_all("g").unwrap();
+    /// theirs.chmod("d", 0o750).unwrap();
+    ///
+    /// let m = base.merge(&ours, &theirs, Resolution::Theirs);
+    /// assert_eq!(&m.tree.paths(), &["/d/", "/f"]);
+    /// assert_eq!(m.tree.stat("f").unwrap().meta.nlink, 1);
+    /// assert_eq!(m.conflicts.len(), 1);
+    /// assert_eq!(m.conflicts[0].path, ["d"]);
+    /// assert_eq!(m.conflicts[0].kind, ConflictKind::BothChanged);
+    /// assert_eq!(m.tree.stat("d").unwrap().meta.mode, 0o750);
+    /// ```
+    pub fn merge<S: MergeStrategy<'a>>(
+        &self,
+        ours: &DTree<'a>,
+        theirs: &DTree<'a>,
+        mut strategy: S,
+    ) -> Merge<'a> {
+        let mut merger = Merger {
+            strategy: &mut strategy,
+            conflicts: Vec::new(),
+            copies: HashMap::new(),
+        };
+        let tree = merger.merge_dirs(Some(self), ours, theirs, &mut Vec::new());
+        tree.recount_links();
+        Merge {
+            tree,
+            conflicts: merger.conflicts,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Entry metadata: timestamps, permission bits, ownership and link counts.
+
+use std::time::SystemTime;
+
+/// Permission bits given to new directories.
+pub const DIR_MODE: u32 = 0o755;
+
+/// Permission bits given to new regular files.
+pub const FILE_MODE: u32 = 0o644;
+
+/// Permission bits given to new symbolic links.
+pub const SYMLINK_MODE: u32 = 0o777;
+
+/// Metadata kept for each directory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// POSIX permission bits, including the set-id and sticky bits.
+    pub mode: u32,
+    /// Owning user.
+    pub uid: u32,
+    /// Owning group.
+    pub gid: u32,
+    /// Number of names referring to the entry. Directory link counts reported by `stat` add
+    /// the `.` and `..` links as well.
+    pub nlink: u64,
+    /// Time of last access.
+    pub atime: SystemTime,
+    /// Time of last modification of the contents.
+    pub mtime: SystemTime,
+    /// Time of last change to the contents or the metadata.
+    pub ctime: SystemTime,
+}
+
+impl Metadata {
+    /// Metadata for a new entry with permission bits `mode`, created at `now` and owned by
+    /// user and group 0.
+    pub fn new(mode: u32, now: SystemTime) -> Self {
+        Metadata {
+            mode,
+            uid: 0,
+            gid: 0,
+            nlink: 1,
+            atime: now,
+            mtime: now,
+            ctime: now,
+        }
+    }
+
+    /// Record that the contents were modified at `now`.
+    pub(crate) fn modified(&mut self, now: SystemTime) {
+        self.mtime = now;
+        self.ctime = now;
+    }
+
+    /// Record that the metadata was changed at `now`.
+    pub(crate) fn changed(&mut self, now: SystemTime) {
+        self.ctime = now;
+    }
+}
+
+/// The kind of node a directory entry refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    /// A directory.
+    Dir,
+    /// A regular file.
+    File,
+    /// A symbolic link.
+    Symlink,
+}
+
+/// Information about an entry, as returned by `stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stat {
+    /// What the entry refers to.
+    pub kind: NodeKind,
+    /// The length of a file or symbolic link target in bytes, or the number of entries in a
+    /// directory.
+    pub len: u64,
+    /// The entry's metadata, with the link count of a directory including `.` and the `..` of
+    /// each subdirectory.
+    pub meta: Metadata,
+}
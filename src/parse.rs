@@ -0,0 +1,259 @@
+//! Parsing a tree from text: the output of `tree(1)`, or an indented outline. Entry names
+//! are borrowed from the text.
+
+use thiserror::Error;
+
+use crate::clock::tree_now;
+use crate::{DPath, DTree, DirError, Node};
+
+/// Why text could not be parsed as a tree.
+#[derive(Error, Debug)]
+pub enum ParseErrorKind<'a> {
+    /// A `tree(1)` line had no branch glyph before its name.
+    #[error("expected a branch such as `├── ` before the name")]
+    NoBranch,
+    /// An outline line was indented to a width matching no enclosing entry, or with tabs.
+    #[error("indentation matches no enclosing entry")]
+    BadIndent,
+    /// A line was nested more than one level below the line before it.
+    #[error("entry is more than one level below the entry before it")]
+    TooDeep,
+    /// The entry could not be added to the tree.
+    #[error("{0}")]
+    Dir(DirError<'a>),
+}
+
+impl<'a> From<DirError<'a>> for ParseErrorKind<'a> {
+    fn from(e: DirError<'a>) -> Self {
+        ParseErrorKind::Dir(e)
+    }
+}
+
+/// An error in text parsed as a tree, with the line and column, both counted from 1, where
+/// it was found.
+#[derive(Error, Debug)]
+#[error("{line}:{column}: {kind}")]
+pub struct ParseError<'a> {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind<'a>,
+}
+
+/// An entry line: where it is, how deep it is, and its text after any indentation.
+struct Line<'a> {
+    line: usize,
+    column: usize,
+    depth: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// An error of the given `kind` at this line.
+    fn error(&self, kind: impl Into<ParseErrorKind<'a>>) -> ParseError<'a> {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            kind: kind.into(),
+        }
+    }
+}
+
+/// True if `c` is a space as `tree(1)` draws it: some versions draw no-break spaces.
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\u{a0}'
+}
+
+/// Strip one level of `tree(1)` indentation or one branch from the start of `line`,
+/// returning the rest and whether a branch was stripped.
+fn strip_cell(line: &str) -> Option<(&str, bool)> {
+    let mut chars = line.chars();
+    let cell: Vec<char> = chars.by_ref().take(4).collect();
+    if cell.len() < 4 || !is_space(cell[3]) {
+        return None;
+    }
+    let rest = chars.as_str();
+    match cell[..3] {
+        ['│' | '|' | ' ' | '\u{a0}', b, c] if is_space(b) && is_space(c) => Some((rest, false)),
+        ['├' | '└', '─', '─'] | ['|' | '`', '-', '-'] => Some((rest, true)),
+        _ => None,
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Build a tree from the output of `tree(1)`, or of [`DTree::render`] with
+    /// [`Render::classify`](crate::Render::classify) set, so that empty directories are
+    /// told from files. The first line
+    /// names the top of the tree and is otherwise ignored, as is everything after the first
+    /// blank line, such as the summary. An entry with entries below it is a directory, as is
+    /// an entry whose name ends in `/`, as drawn by `tree -F`; `name -> target` is a symbolic
+    /// link; any other entry is an empty regular file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let text = "\
+    /// .
+    /// ├── a
+    /// │   ├── b/
+    /// │   └── f
+    /// └── l -> a/b
+    ///
+    /// 2 directories, 2 files";
+    /// let dt = DTree::parse_tree(text).unwrap();
+    /// assert_eq!(&dt.paths_nofollow(), &["/a/b/", "/a/f", "/l"]);
+    /// assert!(dt.with_path("l", |_| ()).is_ok());
+    /// let classified = dt.render().classify(true).to_string();
+    /// assert_eq!(classified, text.replace("── a", "── a/"));
+    /// let copy = DTree::parse_tree(&classified).unwrap();
+    /// assert_eq!(copy.paths_nofollow(), dt.paths_nofollow());
+    ///
+    /// let err = DTree::parse_tree(".\n├── a\n│   │   └── b").unwrap_err();
+    /// assert_eq!((err.line, err.column), (3, 13));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * A [`ParseError`] giving the position of the first line that is not drawn as
+    ///   `tree(1)` draws, or whose entry cannot be added to the tree.
+    pub fn parse_tree(text: &'a str) -> std::result::Result<Self, ParseError<'a>> {
+        let mut lines = Vec::new();
+        for (i, line) in text.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                break;
+            }
+            let mut rest = line;
+            let mut depth = 0;
+            loop {
+                let column = line[..line.len() - rest.len()].chars().count() + 1;
+                let (after, branch) = strip_cell(rest).ok_or(ParseError {
+                    line: i + 1,
+                    column,
+                    kind: ParseErrorKind::NoBranch,
+                })?;
+                depth += 1;
+                rest = after;
+                if branch {
+                    lines.push(Line {
+                        line: i + 1,
+                        column: column + 4,
+                        depth,
+                        text: rest,
+                    });
+                    break;
+                }
+            }
+        }
+        Self::from_lines(&lines)
+    }
+
+    /// Build a tree from an outline with one entry per line, each entry indented with spaces
+    /// further than the directory it is in. Entries are recognized as for
+    /// [`DTree::parse_tree`]. Blank lines are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let dt = DTree::parse_outline(
+    ///     "
+    ///     src
+    ///       lib.rs
+    ///       bin/
+    ///     target/
+    ///     ",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(&dt.paths(), &["/src/lib.rs", "/src/bin/", "/target/"]);
+    ///
+    /// let err = DTree::parse_outline("a\n    b\n  c").unwrap_err();
+    /// assert_eq!((err.line, err.column), (3, 3));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * A [`ParseError`] giving the position of the first line that is indented to a width
+    ///   matching no enclosing entry, or whose entry cannot be added to the tree.
+    pub fn parse_outline(text: &'a str) -> std::result::Result<Self, ParseError<'a>> {
+        let mut lines = Vec::new();
+        let mut indents: Vec<usize> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let entry = line.trim_start_matches(' ');
+            let width = line.len() - entry.len();
+            if entry.trim().is_empty() {
+                continue;
+            }
+            let at = Line {
+                line: i + 1,
+                column: width + 1,
+                depth: 0,
+                text: entry.trim_end(),
+            };
+            if entry.starts_with(char::is_whitespace) {
+                return Err(at.error(ParseErrorKind::BadIndent));
+            }
+            let mut dedented = false;
+            while indents.last().is_some_and(|&w| w > width) {
+                indents.pop();
+                dedented = true;
+            }
+            match indents.last() {
+                Some(&w) if w == width => (),
+                _ if dedented => return Err(at.error(ParseErrorKind::BadIndent)),
+                _ => indents.push(width),
+            }
+            lines.push(Line {
+                depth: indents.len(),
+                ..at
+            });
+        }
+        Self::from_lines(&lines)
+    }
+
+    /// Build a tree from entry lines, each at most one level deeper than the one before.
+    fn from_lines(lines: &[Line<'a>]) -> std::result::Result<Self, ParseError<'a>> {
+        let now = tree_now();
+        let mut dt = DTree::new();
+        let mut dir: Vec<&'a str> = Vec::new();
+        let mut previous: Option<(usize, &'a str, bool)> = None;
+        for (i, l) in lines.iter().enumerate() {
+            let above = previous.map_or(0, |(depth, _, _)| depth);
+            if l.depth > above + 1 {
+                return Err(l.error(ParseErrorKind::TooDeep));
+            }
+            if let Some((depth, name, false)) = previous {
+                if l.depth > depth {
+                    return Err(l.error(DirError::NotADirectory(name)));
+                }
+            }
+            dir.truncate(l.depth - 1);
+            let (name, node) = match l.text.split_once(" -> ") {
+                Some((name, target)) => {
+                    DPath::parse(target).map_err(|e| l.error(e))?;
+                    (name, Node::Symlink(target))
+                }
+                None => {
+                    let nested = lines.get(i + 1).is_some_and(|n| n.depth > l.depth);
+                    match l.text.strip_suffix('/') {
+                        Some(name) => (name, Node::Dir(DTree::new())),
+                        None if nested => (l.text, Node::Dir(DTree::new())),
+                        None => (l.text, Node::File(Vec::new())),
+                    }
+                }
+            };
+            if name == "." || name == ".." {
+                return Err(l.error(DirError::DirExists(name)));
+            }
+            let is_dir = matches!(node, Node::Dir(_));
+            let mut path = dir.clone();
+            path.push(name);
+            dt.add_entry(name, &path, node, now)
+                .map_err(|e| l.error(e))?;
+            if is_dir {
+                dir.push(name);
+            }
+            previous = Some((l.depth, name, is_dir));
+        }
+        Ok(dt)
+    }
+}
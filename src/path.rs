@@ -0,0 +1,107 @@
+//! Path strings: parsing `/`-separated text into component slices borrowed from the input.
+
+use crate::{DirError, Result};
+
+/// A parsed path. Components borrow from the string the path was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DPath<'a> {
+    absolute: bool,
+    components: Vec<&'a str>,
+}
+
+impl<'a> DPath<'a> {
+    /// Parse `path` into its components. A leading `/` makes the path absolute. Empty
+    /// components, as produced by repeated or trailing slashes, are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DPath;
+    /// let p = DPath::parse("/a/b/c").unwrap();
+    /// assert!(p.is_absolute());
+    /// assert_eq!(p.components(), &["a", "b", "c"]);
+    ///
+    /// let p = DPath::parse("b//c/").unwrap();
+    /// assert!(!p.is_absolute());
+    /// assert_eq!(p.components(), &["b", "c"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidPath` if `path` is empty or contains a NUL character.
+    pub fn parse(path: &'a str) -> Result<'a, Self> {
+        if path.is_empty() || path.contains('\0') {
+            return Err(DirError::InvalidPath(path));
+        }
+        let absolute = path.starts_with('/');
+        let components = path.split('/').filter(|c| !c.is_empty()).collect();
+        Ok(DPath {
+            absolute,
+            components,
+        })
+    }
+
+    /// True if the path started with `/`.
+    pub fn is_absolute(&self) -> bool {
+        self.absolute
+    }
+
+    /// The component names of the path, in order.
+    pub fn components(&self) -> &[&'a str] {
+        &self.components
+    }
+
+    /// Resolve this path against the working directory `cwd`, giving the component names of
+    /// the directory it refers to from the root. `.` is dropped and `..` removes the preceding
+    /// component; as in POSIX, `..` at the root stays at the root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DPath;
+    /// let p = DPath::parse("../c/./d").unwrap();
+    /// assert_eq!(p.resolve(&["a", "b"]), &["a", "c", "d"]);
+    /// let p = DPath::parse("/../../x").unwrap();
+    /// assert_eq!(p.resolve(&["a", "b"]), &["x"]);
+    /// ```
+    pub fn resolve(&self, cwd: &[&'a str]) -> Vec<&'a str> {
+        let base = if self.absolute { &[] } else { cwd };
+        resolve(base, &self.components)
+    }
+}
+
+/// Resolve the components of `path` starting from the directory `base`. `.` names the current
+/// directory and is dropped; `..` names the parent and removes the last component, staying at
+/// the root if there is none.
+pub(crate) fn resolve<'a>(base: &[&'a str], path: &[&'a str]) -> Vec<&'a str> {
+    let mut resolved = base.to_vec();
+    for &c in path {
+        match c {
+            "." => (),
+            ".." => {
+                resolved.pop();
+            }
+            _ => resolved.push(c),
+        }
+    }
+    resolved
+}
+
+/// Resolve `.` and `..` in `path` where there is no context above its starting directory.
+///
+/// # Errors
+///
+/// * `DirError::NoParent` if a `..` would leave the starting directory.
+pub(crate) fn normalize<'a>(path: &[&'a str]) -> Result<'a, Vec<&'a str>> {
+    let mut resolved = Vec::with_capacity(path.len());
+    for &c in path {
+        match c {
+            "." => (),
+            ".." => {
+                resolved.pop().ok_or(DirError::NoParent(c))?;
+            }
+            _ => resolved.push(c),
+        }
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,286 @@
+//! `find`-style queries selecting the entries of a tree by composable predicates.
+
+use std::fmt;
+use std::ops::{Bound, Not, RangeBounds};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::{DTree, Glob, NodeKind, Order, Stat, Walk, WalkEntry};
+
+/// A test on an entry, as made by the [`Query`] constructors.
+#[derive(Clone)]
+enum Pred {
+    Any,
+    Name(String),
+    #[cfg(feature = "regex")]
+    NameRegex(regex::Regex),
+    Path(Glob),
+    Depth(Bound<usize>, Bound<usize>),
+    Empty,
+    HasChild(String),
+    Kind(NodeKind),
+    Stat(Rc<dyn Fn(&Stat) -> bool>),
+    And(Box<Pred>, Box<Pred>),
+    Or(Box<Pred>, Box<Pred>),
+    Not(Box<Pred>),
+}
+
+impl fmt::Debug for Pred {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pred::Any => write!(f, "Any"),
+            Pred::Name(name) => f.debug_tuple("Name").field(name).finish(),
+            #[cfg(feature = "regex")]
+            Pred::NameRegex(re) => f.debug_tuple("NameRegex").field(re).finish(),
+            Pred::Path(glob) => f.debug_tuple("Path").field(glob).finish(),
+            Pred::Depth(lo, hi) => f.debug_tuple("Depth").field(lo).field(hi).finish(),
+            Pred::Empty => write!(f, "Empty"),
+            Pred::HasChild(name) => f.debug_tuple("HasChild").field(name).finish(),
+            Pred::Kind(kind) => f.debug_tuple("Kind").field(kind).finish(),
+            Pred::Stat(_) => write!(f, "Stat(..)"),
+            Pred::And(a, b) => f.debug_tuple("And").field(a).field(b).finish(),
+            Pred::Or(a, b) => f.debug_tuple("Or").field(a).field(b).finish(),
+            Pred::Not(a) => f.debug_tuple("Not").field(a).finish(),
+        }
+    }
+}
+
+impl Pred {
+    /// True if `w` passes this test.
+    fn test(&self, w: &WalkEntry<'_>) -> bool {
+        let d = &w.entry;
+        match self {
+            Pred::Any => true,
+            Pred::Name(name) => d.name == name,
+            #[cfg(feature = "regex")]
+            Pred::NameRegex(re) => re.is_match(d.name),
+            Pred::Path(glob) => glob.matches(&w.path),
+            Pred::Depth(lo, hi) => (*lo, *hi).contains(&w.depth),
+            Pred::Empty => match d.subdir() {
+                Some(dt) => dt.children.is_empty(),
+                None => d.is_file() && d.stat().len == 0,
+            },
+            Pred::HasChild(name) => d.subdir().is_some_and(|dt| dt.child(name).is_some()),
+            Pred::Kind(kind) => d.kind() == *kind,
+            Pred::Stat(f) => f(&d.stat()),
+            Pred::And(a, b) => a.test(w) && b.test(w),
+            Pred::Or(a, b) => a.test(w) || b.test(w),
+            Pred::Not(a) => !a.test(w),
+        }
+    }
+
+    /// The greatest depth at which an entry can pass this test, if there is one.
+    fn max_depth(&self) -> Option<usize> {
+        match self {
+            Pred::Depth(_, Bound::Included(hi)) => Some(*hi),
+            Pred::Depth(_, Bound::Excluded(hi)) => Some(hi.saturating_sub(1)),
+            Pred::And(a, b) => match (a.max_depth(), b.max_depth()) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            Pred::Or(a, b) => Some(a.max_depth()?.max(b.max_depth()?)),
+            _ => None,
+        }
+    }
+}
+
+/// The bounds of `range`, owned.
+fn bounds<T: Copy>(range: impl RangeBounds<T>) -> (Bound<T>, Bound<T>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+/// A composable test on the entries of a tree, in the manner of `find`, for [`DTree::find`].
+/// Simple queries are made with the constructors below and combined with [`Query::and`],
+/// [`Query::or`] and `!`. Tests are made on entries as they are, without following symbolic
+/// links.
+#[derive(Debug, Clone)]
+pub struct Query(Pred);
+
+impl Query {
+    /// Match every entry.
+    pub fn any() -> Self {
+        Query(Pred::Any)
+    }
+
+    /// Match entries named `name`.
+    pub fn name(name: &str) -> Self {
+        Query(Pred::Name(name.to_string()))
+    }
+
+    /// Match entries with names the regular expression `re` matches somewhere in; anchor it
+    /// with `^` and `$` to match whole names. Needs the `regex` feature.
+    ///
+    /// # Errors
+    ///
+    /// * A `regex::Error` if `re` is not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn name_regex(re: &str) -> std::result::Result<Self, regex::Error> {
+        Ok(Query(Pred::NameRegex(regex::Regex::new(re)?)))
+    }
+
+    /// Match entries whose paths from the top of the search `glob` matches.
+    pub fn path(glob: Glob) -> Self {
+        Query(Pred::Path(glob))
+    }
+
+    /// Match entries at depths in `range`, where entries at the top of the search are at
+    /// depth 1.
+    pub fn depth(range: impl RangeBounds<usize>) -> Self {
+        let (lo, hi) = bounds(range);
+        Query(Pred::Depth(lo, hi))
+    }
+
+    /// Match directories with no entries and files with no contents.
+    pub fn empty() -> Self {
+        Query(Pred::Empty)
+    }
+
+    /// Match directories with an entry named `name`.
+    pub fn has_child(name: &str) -> Self {
+        Query(Pred::HasChild(name.to_string()))
+    }
+
+    /// Match entries of the given `kind`.
+    pub fn kind(kind: NodeKind) -> Self {
+        Query(Pred::Kind(kind))
+    }
+
+    /// Match entries whose [`Stat`] passes `test`.
+    pub fn stat(test: impl Fn(&Stat) -> bool + 'static) -> Self {
+        Query(Pred::Stat(Rc::new(test)))
+    }
+
+    /// Match entries with lengths in `range`.
+    pub fn len(range: impl RangeBounds<u64>) -> Self {
+        let range = bounds(range);
+        Query::stat(move |s| range.contains(&s.len))
+    }
+
+    /// Match entries with modification times in `range`.
+    pub fn mtime(range: impl RangeBounds<SystemTime>) -> Self {
+        let range = bounds(range);
+        Query::stat(move |s| range.contains(&s.meta.mtime))
+    }
+
+    /// Match entries with all of the permission bits in `mode` set.
+    pub fn mode(mode: u32) -> Self {
+        Query::stat(move |s| s.meta.mode & mode == mode)
+    }
+
+    /// Match entries owned by the user `uid`.
+    pub fn uid(uid: u32) -> Self {
+        Query::stat(move |s| s.meta.uid == uid)
+    }
+
+    /// Match entries owned by the group `gid`.
+    pub fn gid(gid: u32) -> Self {
+        Query::stat(move |s| s.meta.gid == gid)
+    }
+
+    /// Match entries that match both this query and `other`.
+    pub fn and(self, other: Query) -> Self {
+        Query(Pred::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Match entries that match this query, `other`, or both.
+    pub fn or(self, other: Query) -> Self {
+        Query(Pred::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// True if the entry `w`, as visited by a [`Walk`], matches this query.
+    pub fn matches(&self, w: &WalkEntry<'_>) -> bool {
+        self.0.test(w)
+    }
+}
+
+impl Not for Query {
+    type Output = Query;
+
+    /// Match entries that do not match this query.
+    fn not(self) -> Query {
+        Query(Pred::Not(Box::new(self.0)))
+    }
+}
+
+/// A lazy iterator over the entries matching a [`Query`], as returned by [`DTree::find`].
+/// Entries are visited depth-first, each directory before the entries in it, and each path
+/// is given from the top of the tree. Where the query limits the depth of matches, deeper
+/// directories are not looked into. Symbolic links are not followed; a directory with
+/// several names is visited under each.
+#[derive(Debug)]
+pub struct Found<'t, 'a, 'q> {
+    walk: Walk<'t, 'a>,
+    query: &'q Query,
+}
+
+impl<'t, 'a, 'q> Found<'t, 'a, 'q> {
+    /// The paths of the remaining matching entries, in the form of [`DTree::paths`]: from
+    /// the root, with the paths of directories ending in `/`.
+    pub fn paths(self) -> Vec<String> {
+        self.map(|w| {
+            let mut path = format!("/{}", w.path.join("/"));
+            if w.entry.is_dir() {
+                path.push('/');
+            }
+            path
+        })
+        .collect()
+    }
+}
+
+impl<'t, 'a, 'q> Iterator for Found<'t, 'a, 'q> {
+    type Item = WalkEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let query = self.query;
+        self.walk.find(|w| query.matches(w))
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Start a lazy search for the entries below this directory, at every depth and of every
+    /// kind, that match `query`. See [`Found`] for the order and paths of the entries found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, Glob, NodeKind, Query};
+    /// let mut dt = DTree::from_paths(&["/src/lib.rs", "/src/bin/main.rs", "/tmp/", "/README"])
+    ///     .unwrap();
+    /// dt.write("README", b"read me").unwrap();
+    /// dt.chmod("src/bin/main.rs", 0o755).unwrap();
+    ///
+    /// let dirs = Query::kind(NodeKind::Dir);
+    /// assert_eq!(dt.find(&dirs).paths(), ["/src/", "/src/bin/", "/tmp/"]);
+    /// assert_eq!(dt.find(&Query::empty()).paths(), ["/src/lib.rs", "/src/bin/main.rs", "/tmp/"]);
+    ///
+    /// let q = Query::path(Glob::new("**/*.rs").unwrap()).and(Query::mode(0o100));
+    /// assert_eq!(dt.find(&q).paths(), ["/src/bin/main.rs"]);
+    ///
+    /// let q = Query::has_child("bin").or(Query::len(5..)).and(Query::depth(..=1));
+    /// assert_eq!(dt.find(&q).paths(), ["/src/", "/README"]);
+    ///
+    /// let q = Query::kind(NodeKind::File).and(!Query::name("lib.rs"));
+    /// let found: Vec<_> = dt.find(&q).map(|w| (w.path, w.entry.stat().len)).collect();
+    /// assert_eq!(found, [(vec!["src", "bin", "main.rs"], 0), (vec!["README"], 7)]);
+    /// ```
+    ///
+    /// With the `regex` feature, names can be matched by regular expressions.
+    ///
+    /// ```
+    /// # #[cfg(feature = "regex")]
+    /// # {
+    /// # use dtree::{DTree, Query};
+    /// let dt = DTree::from_paths(&["/a/b1/", "/a/b22", "/a/c3"]).unwrap();
+    /// let q = Query::name_regex(r"^b\d+$").unwrap();
+    /// assert_eq!(dt.find(&q).paths(), ["/a/b1/", "/a/b22"]);
+    /// # }
+    /// ```
+    pub fn find<'q>(&self, query: &'q Query) -> Found<'_, 'a, 'q> {
+        let mut walk = self.walk().order(Order::PreOrder).include_dirs(true);
+        if let Some(depth) = query.0.max_depth() {
+            walk = walk.max_depth(depth);
+        }
+        Found { walk, query }
+    }
+}
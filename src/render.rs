@@ -0,0 +1,216 @@
+//! Rendering a tree as text in the style of `tree(1)`.
+
+use std::fmt;
+
+use crate::{DEnt, DTree};
+
+/// The strings drawn before each entry name.
+struct Glyphs {
+    /// Before an entry with more entries after it.
+    tee: &'static str,
+    /// Before the last entry of a directory.
+    elbow: &'static str,
+    /// Below an entry with more entries after it.
+    pipe: &'static str,
+    /// Below the last entry of a directory.
+    blank: &'static str,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    tee: "├── ",
+    elbow: "└── ",
+    pipe: "│   ",
+    blank: "    ",
+};
+
+const ASCII: Glyphs = Glyphs {
+    tee: "|-- ",
+    elbow: "`-- ",
+    pipe: "|   ",
+    blank: "    ",
+};
+
+/// A `tree(1)`-style rendering of a [`DTree`], as returned by [`DTree::render`], written out
+/// through its [`Display`](fmt::Display) implementation. The top of the tree is drawn as `.`;
+/// symbolic links are drawn as `name -> target` and counted as files. By default box-drawing
+/// glyphs are used, every depth is drawn, entries keep the order they were made in, directory
+/// names are drawn bare, and a summary line ends the rendering. Lines are separated, not
+/// terminated, by newlines.
+///
+/// [`DTree::parse_tree`] reads a rendering back only if it is classified, with
+/// [`Render::classify`], and draws every depth: unclassified, an empty directory is drawn
+/// just as an empty file is, and is read back as one.
+#[derive(Debug, Clone, Copy)]
+pub struct Render<'t, 'a> {
+    tree: &'t DTree<'a>,
+    ascii: bool,
+    max_depth: Option<usize>,
+    sorted: bool,
+    classify: bool,
+    summary: bool,
+}
+
+impl<'t, 'a> Render<'t, 'a> {
+    /// Start a rendering of `tree` with the default options.
+    pub(crate) fn new(tree: &'t DTree<'a>) -> Self {
+        Render {
+            tree,
+            ascii: false,
+            max_depth: None,
+            sorted: false,
+            classify: false,
+            summary: true,
+        }
+    }
+
+    /// Draw with ASCII characters only, if `ascii` is set.
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Do not draw entries below `depth`; entries at the top of the tree have depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Draw the entries of each directory sorted by name, if `sorted` is set.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Draw directory names followed by `/`, as `tree -F` does, if `classify` is set.
+    pub fn classify(mut self, classify: bool) -> Self {
+        self.classify = classify;
+        self
+    }
+
+    /// End with a line counting the directories and files drawn, if `summary` is set.
+    pub fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Draw the entries of `dt`, at `depth`, each line starting with `prefix`, and add the
+    /// directories and files drawn to `counts`.
+    fn entries(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        dt: &DTree<'a>,
+        prefix: &str,
+        depth: usize,
+        counts: &mut (usize, usize),
+    ) -> fmt::Result {
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return Ok(());
+        }
+        let glyphs = if self.ascii { &ASCII } else { &UNICODE };
+        let mut children: Vec<&DEnt<'a>> = dt.children.iter().collect();
+        if self.sorted {
+            children.sort_by_key(|d| d.name);
+        }
+        for (i, d) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let glyph = if last { glyphs.elbow } else { glyphs.tee };
+            write!(f, "\n{}{}{}", prefix, glyph, d.name)?;
+            if let Some(target) = d.link_target() {
+                write!(f, " -> {}", target)?;
+                counts.1 += 1;
+            } else if let Some(subdir) = d.subdir() {
+                if self.classify {
+                    write!(f, "/")?;
+                }
+                counts.0 += 1;
+                let below = if last { glyphs.blank } else { glyphs.pipe };
+                let prefix = format!("{}{}", prefix, below);
+                self.entries(f, &subdir, &prefix, depth + 1, counts)?;
+            } else {
+                counts.1 += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `count` followed by the `one` or `many` form of a noun.
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+impl<'t, 'a> fmt::Display for Render<'t, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ".")?;
+        let mut counts = (0, 0);
+        self.entries(f, self.tree, "", 1, &mut counts)?;
+        if self.summary {
+            let (dirs, files) = counts;
+            write!(
+                f,
+                "\n\n{}, {}",
+                plural(dirs, "directory", "directories"),
+                plural(files, "file", "files"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for DTree<'a> {
+    /// Draw the tree as `tree(1)` would, with the default options of [`Render`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.mkdir("c").unwrap();
+    /// let expected = "\
+    /// .
+    /// ├── a
+    /// │   ├── b
+    /// │   └── f
+    /// └── c
+    ///
+    /// 3 directories, 1 file";
+    /// assert_eq!(dt.to_string(), expected);
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render().fmt(f)
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Start a `tree(1)`-style rendering of this directory. See [`Render`] for the options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["z", "y", "x"]).unwrap();
+    /// dt.mkdir("b").unwrap();
+    /// dt.symlink("z/y", "a").unwrap();
+    /// let r = dt.render().ascii(true).sorted(true).max_depth(2).summary(false);
+    /// let expected = "\
+    /// .
+    /// |-- a -> z/y
+    /// |-- b
+    /// `-- z
+    ///     `-- y";
+    /// assert_eq!(r.to_string(), expected);
+    /// let expected = "\
+    /// .
+    /// |-- a -> z/y
+    /// |-- b/
+    /// `-- z/
+    ///     `-- y/";
+    /// assert_eq!(r.classify(true).to_string(), expected);
+    /// ```
+    pub fn render(&self) -> Render<'_, 'a> {
+        Render::new(self)
+    }
+}
@@ -0,0 +1,119 @@
+//! Path resolution: turning a path into the canonical components of the entry it names,
+//! following symbolic links along the way.
+
+use crate::{DPath, DTree, DirError, Node, NodeKind, Result};
+
+/// The most symbolic links that will be followed while resolving a single path.
+pub const SYMLOOP_MAX: usize = 40;
+
+impl<'a> DTree<'a> {
+    /// Resolve the components of `path`, starting at the directory given by the canonical
+    /// components `dir`, into the canonical components of the entry it names: components
+    /// with no `.`, `..` or symbolic links. `..` at the top of the tree stays there if `clamp`
+    /// is set, and fails otherwise. Symbolic links met before the last component are always
+    /// followed, with absolute targets taken from the top of the tree; a link as the last
+    /// component is only followed if `follow` is set. The last component need not exist.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if a component other than the last does not exist.
+    /// * `DirError::NotADirectory` if a component other than the last is a file.
+    /// * `DirError::NoParent` if a `..` would leave the tree and `clamp` is not set.
+    /// * `DirError::TooManyLinks` if more than [`SYMLOOP_MAX`] links are followed.
+    pub(crate) fn lookup(
+        &self,
+        mut dir: Vec<&'a str>,
+        path: &[&'a str],
+        follow: bool,
+        clamp: bool,
+    ) -> Result<'a, Vec<&'a str>> {
+        let mut pending: Vec<&'a str> = path.iter().rev().copied().collect();
+        let mut hops = 0;
+        while let Some(name) = pending.pop() {
+            match name {
+                "." => continue,
+                ".." => {
+                    if dir.pop().is_none() && !clamp {
+                        return Err(DirError::NoParent(name));
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+            let last = pending.is_empty();
+            let found = self.in_dir(&dir, |dt| {
+                dt.child(name).map(|d| (d.kind(), d.link_target()))
+            })?;
+            match found {
+                Some((_, Some(target))) if follow || !last => {
+                    hops += 1;
+                    if hops > SYMLOOP_MAX {
+                        return Err(DirError::TooManyLinks(name));
+                    }
+                    let target = DPath::parse(target)?;
+                    if target.is_absolute() {
+                        dir.clear();
+                    }
+                    pending.extend(target.components().iter().rev());
+                }
+                Some((NodeKind::File, _)) if !last => return Err(DirError::NotADirectory(name)),
+                None if !last => return Err(DirError::InvalidChild(name)),
+                _ => dir.push(name),
+            }
+        }
+        Ok(dir)
+    }
+
+    /// Call `f` on the directory given by the canonical components `dir`.
+    ///
+    /// # Errors
+    ///
+    /// * `DirError::InvalidChild` if a component does not exist.
+    /// * `DirError::NotADirectory` if a component is not a directory.
+    pub(crate) fn in_dir<F, R>(&self, dir: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&DTree<'a>) -> R,
+    {
+        let (&name, rest) = match dir.split_first() {
+            Some(split) => split,
+            None => return Ok(f(self)),
+        };
+        let d = self.child(name).ok_or(DirError::InvalidChild(name))?;
+        let inode = d.inode.borrow();
+        match &inode.node {
+            Node::Dir(dt) => dt.in_dir(rest, f),
+            _ => Err(DirError::NotADirectory(name)),
+        }
+    }
+
+    /// Call `f` on the directory given by the canonical components `dir` mutably.
+    ///
+    /// # Errors
+    ///
+    /// As for [`DTree::in_dir`].
+    pub(crate) fn in_dir_mut<F, R>(&mut self, dir: &[&'a str], f: F) -> Result<'a, R>
+    where
+        F: FnOnce(&mut DTree<'a>) -> R,
+    {
+        let (&name, rest) = match dir.split_first() {
+            Some(split) => split,
+            None => return Ok(f(self)),
+        };
+        let d = self.child(name).ok_or(DirError::InvalidChild(name))?;
+        let mut inode = d.inode.borrow_mut();
+        match &mut inode.node {
+            Node::Dir(dt) => dt.in_dir_mut(rest, f),
+            _ => Err(DirError::NotADirectory(name)),
+        }
+    }
+
+    /// True if the directory at `target` is this directory or is below it, through any of
+    /// the names of the directories between.
+    pub(crate) fn reaches(&self, target: *const DTree<'a>) -> bool {
+        std::ptr::eq(self, target)
+            || self
+                .children
+                .iter()
+                .any(|d| d.subdir().is_some_and(|dt| dt.reaches(target)))
+    }
+}
@@ -0,0 +1,499 @@
+//! A compact, versioned binary encoding of trees and operating system state.
+//!
+//! A snapshot is laid out as follows, with every integer little-endian:
+//!
+//! * The magic number `b"DTRE"` and a `u16` format version.
+//! * A `u8` saying what was saved: 0 for a [`DTree`], 1 for an [`OsState`].
+//! * A string table: a `u32` count, then each string as a `u32` byte length and its UTF-8
+//!   bytes. Each distinct name or symbolic link target is stored once.
+//! * The node records: a `u32` count, then each node as a `u8` kind (0 directory, 1 file,
+//!   2 symbolic link), its metadata, and then for a directory a `u32` count of entries
+//!   each given as a `u32` string index and a `u32` node index, for a file a `u32` length and
+//!   its contents, and for a symbolic link the `u32` string index of its target. Metadata is
+//!   the `u32` mode, uid and gid, the `u64` link count, and the access, modification and
+//!   change times, each as `i64` seconds and `u32` nanoseconds from the Unix epoch. Link
+//!   counts are not trusted on loading, but counted again from the names in the tree. A
+//!   directory only refers to nodes recorded before it, so nodes shared by several names
+//!   are stored once and no directory can contain itself.
+//! * The entries of the top of the tree, stored as for a directory.
+//! * For operating system state, a `u32` count and the string indices of the components of
+//!   the working directory.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use thiserror::Error;
+
+use crate::{check_name, DEnt, DPath, DTree, DirError, Inode, Metadata, Node, OsState};
+
+/// The bytes every snapshot starts with.
+const MAGIC: &[u8; 4] = b"DTRE";
+
+/// The format version written, and the only one read.
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// What a snapshot holds.
+const TREE: u8 = 0;
+const OS_STATE: u8 = 1;
+
+/// Node record kinds.
+const DIR: u8 = 0;
+const FILE: u8 = 1;
+const SYMLINK: u8 = 2;
+
+/// Why a snapshot could not be loaded.
+#[derive(Error, Debug)]
+pub enum SnapshotError<'a> {
+    /// The input does not start with the snapshot magic number.
+    #[error("not a snapshot")]
+    BadMagic,
+    /// The snapshot was written in a format version this crate cannot read.
+    #[error("unsupported snapshot version {0}")]
+    UnsupportedVersion(u16),
+    /// The snapshot holds something other than what was asked for.
+    #[error("snapshot holds the wrong kind of value ({0})")]
+    WrongKind(u8),
+    /// The input ended in the middle of the snapshot.
+    #[error("snapshot truncated at byte {0}")]
+    Truncated(usize),
+    /// The input continued past the end of the snapshot.
+    #[error("unexpected data at byte {0}")]
+    TrailingBytes(usize),
+    /// A string in the string table is not UTF-8.
+    #[error("string at byte {0} is not UTF-8")]
+    BadString(usize),
+    /// A string index is past the end of the string table.
+    #[error("no string with index {0}")]
+    BadStringIndex(u32),
+    /// A node index does not refer to a node recorded earlier.
+    #[error("no earlier node with index {0}")]
+    BadNodeIndex(u32),
+    /// A node record has an unknown kind.
+    #[error("unknown node kind {0}")]
+    BadNodeKind(u8),
+    /// A timestamp is out of range.
+    #[error("timestamp at byte {0} is out of range")]
+    BadTime(usize),
+    /// The decoded tree is invalid, for instance with two entries of the same name.
+    #[error("{0}")]
+    Tree(DirError<'a>),
+}
+
+impl<'a> From<DirError<'a>> for SnapshotError<'a> {
+    fn from(e: DirError<'a>) -> Self {
+        SnapshotError::Tree(e)
+    }
+}
+
+/// Result type for loading snapshots.
+type Result<'a, T> = std::result::Result<T, SnapshotError<'a>>;
+
+/// State for writing a snapshot.
+#[derive(Default)]
+struct Writer<'a> {
+    strings: Vec<&'a str>,
+    string_ids: HashMap<&'a str, u32>,
+    node_ids: HashMap<*const RefCell<Inode<'a>>, u32>,
+    node_count: u32,
+    nodes: Vec<u8>,
+}
+
+fn put_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_len(buf: &mut Vec<u8>, n: usize) {
+    put_u32(
+        buf,
+        u32::try_from(n).expect("snapshot lengths fit in 32 bits"),
+    );
+}
+
+fn put_time(buf: &mut Vec<u8>, t: SystemTime) {
+    let (secs, nanos) = match t.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                n => (-(d.as_secs() as i64) - 1, 1_000_000_000 - n),
+            }
+        }
+    };
+    buf.extend_from_slice(&secs.to_le_bytes());
+    put_u32(buf, nanos);
+}
+
+impl<'a> Writer<'a> {
+    /// The string table index of `s`, adding it if need be.
+    fn string(&mut self, s: &'a str) -> u32 {
+        if let Some(&id) = self.string_ids.get(s) {
+            return id;
+        }
+        let id = self.strings.len() as u32;
+        self.strings.push(s);
+        self.string_ids.insert(s, id);
+        id
+    }
+
+    /// Record the entries of `dt` in `buf`, recording their nodes first.
+    fn entries(&mut self, dt: &DTree<'a>, buf: &mut Vec<u8>) {
+        let entries: Vec<(u32, u32)> = dt
+            .children
+            .iter()
+            .map(|d| (self.string(d.name), self.node(d)))
+            .collect();
+        put_len(buf, entries.len());
+        for (name, node) in entries {
+            put_u32(buf, name);
+            put_u32(buf, node);
+        }
+    }
+
+    /// The node index of the node `d` names, recording it if need be.
+    fn node(&mut self, d: &DEnt<'a>) -> u32 {
+        let key = Rc::as_ptr(&d.inode);
+        if let Some(&id) = self.node_ids.get(&key) {
+            return id;
+        }
+        let inode = d.inode.borrow();
+        let mut rec = Vec::new();
+        match &inode.node {
+            Node::Dir(dt) => {
+                rec.push(DIR);
+                put_meta(&mut rec, &inode.meta);
+                self.entries(dt, &mut rec);
+            }
+            Node::File(contents) => {
+                rec.push(FILE);
+                put_meta(&mut rec, &inode.meta);
+                put_len(&mut rec, contents.len());
+                rec.extend_from_slice(contents);
+            }
+            Node::Symlink(target) => {
+                rec.push(SYMLINK);
+                put_meta(&mut rec, &inode.meta);
+                let target = self.string(target);
+                put_u32(&mut rec, target);
+            }
+        }
+        let id = self.node_count;
+        self.node_count += 1;
+        self.nodes.extend(rec);
+        self.node_ids.insert(key, id);
+        id
+    }
+
+    /// Write out a snapshot of `kind` holding `dt`, followed by `tail`.
+    fn finish(mut self, kind: u8, dt: &DTree<'a>, tail: &[&'a str]) -> Vec<u8> {
+        let mut top = Vec::new();
+        self.entries(dt, &mut top);
+        if kind == OS_STATE {
+            put_len(&mut top, tail.len());
+            for s in tail {
+                let id = self.string(s);
+                put_u32(&mut top, id);
+            }
+        }
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        buf.push(kind);
+        put_len(&mut buf, self.strings.len());
+        for s in &self.strings {
+            put_len(&mut buf, s.len());
+            buf.extend_from_slice(s.as_bytes());
+        }
+        put_u32(&mut buf, self.node_count);
+        buf.extend(self.nodes);
+        buf.extend(top);
+        buf
+    }
+}
+
+fn put_meta(buf: &mut Vec<u8>, meta: &Metadata) {
+    put_u32(buf, meta.mode);
+    put_u32(buf, meta.uid);
+    put_u32(buf, meta.gid);
+    buf.extend_from_slice(&meta.nlink.to_le_bytes());
+    put_time(buf, meta.atime);
+    put_time(buf, meta.mtime);
+    put_time(buf, meta.ctime);
+}
+
+/// State for reading a snapshot.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    strings: Vec<&'a str>,
+    nodes: Vec<Rc<RefCell<Inode<'a>>>>,
+}
+
+impl<'a> Reader<'a> {
+    /// Check the header of `buf`, expecting a snapshot of `kind`.
+    fn new(buf: &'a [u8], kind: u8) -> Result<'a, Self> {
+        let mut r = Reader {
+            buf,
+            pos: 0,
+            strings: Vec::new(),
+            nodes: Vec::new(),
+        };
+        if r.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes(r.array()?);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let found = r.u8()?;
+        if found != kind {
+            return Err(SnapshotError::WrongKind(found));
+        }
+        Ok(r)
+    }
+
+    fn take(&mut self, n: usize) -> Result<'a, &'a [u8]> {
+        if self.buf.len() - self.pos < n {
+            return Err(SnapshotError::Truncated(self.buf.len()));
+        }
+        let bytes = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<'a, [u8; N]> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<'a, u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<'a, u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// A count of items, each at least `size` bytes long, that must fit in what is left.
+    fn count(&mut self, size: usize) -> Result<'a, usize> {
+        let n = self.u32()? as usize;
+        if n.saturating_mul(size) > self.buf.len() - self.pos {
+            return Err(SnapshotError::Truncated(self.buf.len()));
+        }
+        Ok(n)
+    }
+
+    fn time(&mut self) -> Result<'a, SystemTime> {
+        let at = self.pos;
+        let secs = i64::from_le_bytes(self.array()?);
+        let nanos = self.u32()?;
+        let bad = SnapshotError::BadTime(at);
+        if nanos >= 1_000_000_000 {
+            return Err(bad);
+        }
+        let epoch = SystemTime::UNIX_EPOCH;
+        let t = if secs >= 0 {
+            epoch.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            epoch
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|t| t.checked_add(Duration::from_nanos(nanos.into())))
+        };
+        t.ok_or(bad)
+    }
+
+    fn meta(&mut self) -> Result<'a, Metadata> {
+        Ok(Metadata {
+            mode: self.u32()?,
+            uid: self.u32()?,
+            gid: self.u32()?,
+            nlink: u64::from_le_bytes(self.array()?),
+            atime: self.time()?,
+            mtime: self.time()?,
+            ctime: self.time()?,
+        })
+    }
+
+    /// The string with the index read next.
+    fn string(&mut self) -> Result<'a, &'a str> {
+        let id = self.u32()?;
+        self.strings
+            .get(id as usize)
+            .copied()
+            .ok_or(SnapshotError::BadStringIndex(id))
+    }
+
+    fn string_table(&mut self) -> Result<'a, ()> {
+        let n = self.count(4)?;
+        self.strings.reserve(n);
+        for _ in 0..n {
+            let len = self.u32()? as usize;
+            let at = self.pos;
+            let bytes = self.take(len)?;
+            let s = std::str::from_utf8(bytes).map_err(|_| SnapshotError::BadString(at))?;
+            self.strings.push(s);
+        }
+        Ok(())
+    }
+
+    /// A directory's entries, which may only name nodes already read.
+    fn entries(&mut self) -> Result<'a, DTree<'a>> {
+        let n = self.count(8)?;
+        let mut children = Vec::with_capacity(n);
+        for _ in 0..n {
+            let name = self.string()?;
+            let id = self.u32()?;
+            let inode = self
+                .nodes
+                .get(id as usize)
+                .ok_or(SnapshotError::BadNodeIndex(id))?;
+            children.push(DEnt {
+                name,
+                inode: Rc::clone(inode),
+            });
+        }
+        let dt = DTree { children };
+        dt.check_children()?;
+        Ok(dt)
+    }
+
+    fn node_table(&mut self) -> Result<'a, ()> {
+        let n = self.count(1)?;
+        self.nodes.reserve(n);
+        for _ in 0..n {
+            let kind = self.u8()?;
+            let meta = self.meta()?;
+            let node = match kind {
+                DIR => Node::Dir(self.entries()?),
+                FILE => {
+                    let len = self.u32()? as usize;
+                    Node::File(self.take(len)?.to_vec())
+                }
+                SYMLINK => {
+                    let target = self.string()?;
+                    DPath::parse(target)?;
+                    Node::Symlink(target)
+                }
+                kind => return Err(SnapshotError::BadNodeKind(kind)),
+            };
+            self.nodes.push(Rc::new(RefCell::new(Inode { node, meta })));
+        }
+        Ok(())
+    }
+
+    /// Read the string and node tables and the top of the tree.
+    fn tree(&mut self) -> Result<'a, DTree<'a>> {
+        self.string_table()?;
+        self.node_table()?;
+        let dt = self.entries()?;
+        dt.recount_links();
+        Ok(dt)
+    }
+
+    fn finish(self) -> Result<'a, ()> {
+        if self.pos < self.buf.len() {
+            return Err(SnapshotError::TrailingBytes(self.pos));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Encode this tree as a snapshot. Nodes with several names are stored once, and are
+    /// shared again when the snapshot is loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DTree, LinkMode};
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["a", "b"]).unwrap();
+    /// dt.create("a/f").unwrap();
+    /// dt.write("a/f", b"data").unwrap();
+    /// dt.link("a/f", "g", LinkMode::FilesOnly).unwrap();
+    /// let snapshot = dt.to_snapshot();
+    ///
+    /// let mut copy = DTree::from_snapshot(&snapshot).unwrap();
+    /// assert_eq!(copy.paths(), dt.paths());
+    /// assert_eq!(copy.stat("a/f").unwrap(), dt.stat("a/f").unwrap());
+    /// copy.write("g", b"changed").unwrap();
+    /// assert_eq!(copy.read("a/f").unwrap(), b"changed");
+    ///
+    /// assert!(DTree::from_snapshot(&snapshot[..snapshot.len() - 1]).is_err());
+    /// assert!(DTree::from_snapshot(b"DTRE\x02\x00\x00").is_err());
+    /// ```
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        Writer::default().finish(TREE, self, &[])
+    }
+
+    /// Load a tree from a snapshot made by [`DTree::to_snapshot`]. Names and symbolic link
+    /// targets are borrowed from `buf`.
+    ///
+    /// # Errors
+    ///
+    /// * A [`SnapshotError`] if `buf` is not a tree snapshot in a supported version, is
+    ///   truncated or corrupt, or decodes to an invalid tree.
+    pub fn from_snapshot(buf: &'a [u8]) -> Result<'a, Self> {
+        let mut r = Reader::new(buf, TREE)?;
+        let dt = r.tree()?;
+        r.finish()?;
+        Ok(dt)
+    }
+}
+
+impl<'a> OsState<'a> {
+    /// Encode the directory tree and working directory as a snapshot. The clock is not
+    /// saved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::OsState;
+    /// let mut s = OsState::new();
+    /// s.mkdir_all(&["a", "b"]).unwrap();
+    /// s.chdir_path("a/b").unwrap();
+    /// let snapshot = s.to_snapshot();
+    /// let copy = OsState::from_snapshot(&snapshot).unwrap();
+    /// assert_eq!(&copy.cwd, &["a", "b"]);
+    /// assert_eq!(copy.dtree.paths(), s.dtree.paths());
+    /// assert!(dtree::DTree::from_snapshot(&snapshot).is_err());
+    ///
+    /// s.chdir_path("/").unwrap();
+    /// s.create("f").unwrap();
+    /// s.cwd = vec!["f"];
+    /// assert!(OsState::from_snapshot(&s.to_snapshot()).is_err());
+    /// ```
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        Writer::default().finish(OS_STATE, &self.dtree, &self.cwd)
+    }
+
+    /// Load state from a snapshot made by [`OsState::to_snapshot`], stamping entries using the
+    /// [`SystemClock`](crate::SystemClock). Names are borrowed from `buf`.
+    ///
+    /// # Errors
+    ///
+    /// * A [`SnapshotError`] if `buf` is not an operating system state snapshot in a supported
+    ///   version, is truncated or corrupt, or decodes to an invalid tree or to a working
+    ///   directory that is not a directory in the tree.
+    pub fn from_snapshot(buf: &'a [u8]) -> Result<'a, Self> {
+        let mut r = Reader::new(buf, OS_STATE)?;
+        let dtree = r.tree()?;
+        let n = r.count(4)?;
+        let mut cwd = Vec::with_capacity(n);
+        for _ in 0..n {
+            cwd.push(r.string()?);
+        }
+        r.finish()?;
+        for c in &cwd {
+            check_name(c)?;
+        }
+        dtree.in_dir(&cwd, |_| ())?;
+        let mut s = OsState::new();
+        s.dtree = dtree;
+        s.cwd = cwd;
+        Ok(s)
+    }
+}
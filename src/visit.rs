@@ -0,0 +1,261 @@
+//! Visitors: callbacks for each directory and entry of a tree, able to prune the walk or end
+//! it early, or to change the tree as it is walked.
+
+use std::time::SystemTime;
+
+use crate::clock::tree_now;
+use crate::{check_name, release, DEnt, DTree, DirError, Result};
+
+/// What a [`Visitor`] wants to happen next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitFlow {
+    /// Carry on with the walk.
+    Continue,
+    /// From [`Visitor::enter_dir`], skip everything in the directory, and do not leave it.
+    /// From the other hooks, skip the remaining entries of the directory containing what was
+    /// visited.
+    Skip,
+    /// End the whole walk.
+    Stop,
+}
+
+/// Callbacks for [`DTree::visit`]. Each hook is given the component names leading to what it
+/// visits from the top of the walk; the top itself has an empty path. Every hook does nothing
+/// by default.
+pub trait Visitor<'a> {
+    /// Called on reaching the directory `dir`, before anything in it.
+    fn enter_dir(&mut self, _path: &[&'a str], _dir: &DTree<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    /// Called on the directory `dir` after everything in it.
+    fn leave_dir(&mut self, _path: &[&'a str], _dir: &DTree<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    /// Called on each entry that is not a directory: files and symbolic links, which are not
+    /// followed.
+    fn visit_entry(&mut self, _path: &[&'a str], _entry: &DEnt<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+}
+
+impl<'a> DTree<'a> {
+    /// Walk this directory depth-first, calling the hooks of `visitor` as each directory is
+    /// entered and left and as each other entry is reached. A directory with several names is
+    /// visited under each. Returns `VisitFlow::Stop` if the visitor ended the walk, and
+    /// `VisitFlow::Continue` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::{DEnt, DTree, VisitFlow, Visitor};
+    /// struct FindBig<'a> {
+    ///     found: Option<Vec<&'a str>>,
+    /// }
+    ///
+    /// impl<'a> Visitor<'a> for FindBig<'a> {
+    ///     fn enter_dir(&mut self, path: &[&'a str], _: &DTree<'a>) -> VisitFlow {
+    ///         match path.last() {
+    ///             Some(&".git") => VisitFlow::Skip,
+    ///             _ => VisitFlow::Continue,
+    ///         }
+    ///     }
+    ///
+    ///     fn visit_entry(&mut self, path: &[&'a str], entry: &DEnt<'a>) -> VisitFlow {
+    ///         if entry.stat().len < 4 {
+    ///             return VisitFlow::Continue;
+    ///         }
+    ///         self.found = Some(path.to_vec());
+    ///         VisitFlow::Stop
+    ///     }
+    /// }
+    ///
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&[".git", "objects"]).unwrap();
+    /// dt.create(".git/objects/pack").unwrap();
+    /// dt.write(".git/objects/pack", b"large").unwrap();
+    /// dt.mkdir("src").unwrap();
+    /// dt.create("src/lib.rs").unwrap();
+    /// dt.write("src/lib.rs", b"fn f() {}").unwrap();
+    /// dt.create("src/main.rs").unwrap();
+    ///
+    /// let mut v = FindBig { found: None };
+    /// assert_eq!(dt.visit(&mut v), VisitFlow::Stop);
+    /// assert_eq!(v.found.unwrap(), &["src", "lib.rs"]);
+    /// ```
+    pub fn visit<V: Visitor<'a>>(&self, visitor: &mut V) -> VisitFlow {
+        let mut path = Vec::new();
+        match self.visit_dir(&mut path, visitor) {
+            VisitFlow::Stop => VisitFlow::Stop,
+            _ => VisitFlow::Continue,
+        }
+    }
+
+    /// Visit this directory, at `path`, with `visitor`, returning what the visitor wants done
+    /// with the rest of the directory containing this one.
+    fn visit_dir<V: Visitor<'a>>(&self, path: &mut Vec<&'a str>, visitor: &mut V) -> VisitFlow {
+        match visitor.enter_dir(path, self) {
+            VisitFlow::Continue => (),
+            VisitFlow::Skip => return VisitFlow::Continue,
+            VisitFlow::Stop => return VisitFlow::Stop,
+        }
+        for d in &self.children {
+            path.push(d.name);
+            let flow = match d.subdir() {
+                Some(dt) => dt.visit_dir(path, visitor),
+                None => visitor.visit_entry(path, d),
+            };
+            path.pop();
+            match flow {
+                VisitFlow::Continue => (),
+                VisitFlow::Skip => break,
+                VisitFlow::Stop => return VisitFlow::Stop,
+            }
+        }
+        visitor.leave_dir(path, self)
+    }
+
+    /// Walk this directory depth-first, calling `f` on each directory with its path from the
+    /// top of the walk, after the directories below it have been walked. `f` may add, remove
+    /// and rename entries in the directory it is given: what it leaves is what the walk of
+    /// the directory containing it sees. Removing entries through [`DTree`] methods such as
+    /// [`DTree::remove_all`] keeps link counts right. A directory with several names is
+    /// walked under each.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["tmp", "tmp"]).unwrap();
+    /// dt.mkdir_all(&["a", "tmp"]).unwrap();
+    /// dt.mkdir_all(&["b", "tmp", "keep"]).unwrap();
+    /// dt.walk_mut(|_, dt| {
+    ///     let empty_tmp = dt
+    ///         .children
+    ///         .iter()
+    ///         .any(|d| d.name == "tmp" && d.subdir().is_some_and(|dt| dt.children.is_empty()));
+    ///     if empty_tmp {
+    ///         dt.remove_all("tmp")?;
+    ///     }
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/", "/b/tmp/keep/"]);
+    ///
+    /// assert!(dt.walk_mut(|_, dt| {
+    ///     dt.children.iter_mut().for_each(|d| d.name = "same");
+    ///     Ok(())
+    /// })
+    /// .is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned by `f`, which ends the walk.
+    /// * `DirError::SlashInName` or `DirError::InvalidName` if `f` leaves an entry with an
+    ///   invalid name.
+    /// * `DirError::DirExists` if `f` leaves two entries with the same name, or one named `.`
+    ///   or `..`.
+    pub fn walk_mut<F>(&mut self, mut f: F) -> Result<'a, ()>
+    where
+        F: FnMut(&[&'a str], &mut DTree<'a>) -> Result<'a, ()>,
+    {
+        self.walk_dirs_mut(&mut Vec::new(), &mut f)
+    }
+
+    /// Walk this directory, at `path`, calling `f` as for [`DTree::walk_mut`].
+    fn walk_dirs_mut<F>(&mut self, path: &mut Vec<&'a str>, f: &mut F) -> Result<'a, ()>
+    where
+        F: FnMut(&[&'a str], &mut DTree<'a>) -> Result<'a, ()>,
+    {
+        for d in &mut self.children {
+            let name = d.name;
+            if let Some(mut dt) = d.subdir_mut() {
+                path.push(name);
+                let walked = dt.walk_dirs_mut(path, f);
+                path.pop();
+                walked?;
+            }
+        }
+        f(path, self)?;
+        self.check_children()
+    }
+
+    /// Check that the entries of this directory have valid, distinct names.
+    pub(crate) fn check_children(&self) -> Result<'a, ()> {
+        for (i, d) in self.children.iter().enumerate() {
+            check_name(d.name)?;
+            let taken = self.children[..i].iter().any(|e| e.name == d.name);
+            if taken || d.name == "." || d.name == ".." {
+                return Err(DirError::DirExists(d.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove every entry below this directory for which `f` returns false, given the entry
+    /// and its path from the top of the walk. Directories are pruned bottom-up, so `f` sees
+    /// each directory as it is once the entries below it have been pruned, and nothing below
+    /// a removed directory is visited again. Each directory that loses entries is recorded as
+    /// modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use dtree::DTree;
+    /// let mut dt = DTree::new();
+    /// dt.mkdir_all(&["tmp", "tmp"]).unwrap();
+    /// dt.mkdir_all(&["a", "tmp"]).unwrap();
+    /// dt.mkdir_all(&["b", "tmp", "keep"]).unwrap();
+    /// dt.retain(|_, d| {
+    ///     d.name != "tmp" || d.subdir().is_some_and(|dt| !dt.children.is_empty())
+    /// });
+    /// let mut paths = dt.paths();
+    /// paths.sort();
+    /// assert_eq!(&paths, &["/a/", "/b/tmp/keep/"]);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[&'a str], &DEnt<'a>) -> bool,
+    {
+        self.retain_entries(&mut Vec::new(), &mut f, tree_now());
+    }
+
+    /// Prune the entries of this directory, at `path`, as for [`DTree::retain`], at time
+    /// `now`. Returns true if any entries of this directory were removed.
+    fn retain_entries<F>(&mut self, path: &mut Vec<&'a str>, f: &mut F, now: SystemTime) -> bool
+    where
+        F: FnMut(&[&'a str], &DEnt<'a>) -> bool,
+    {
+        for d in &mut self.children {
+            let name = d.name;
+            let pruned = d.subdir_mut().is_some_and(|mut dt| {
+                path.push(name);
+                let pruned = dt.retain_entries(path, f, now);
+                path.pop();
+                pruned
+            });
+            if pruned {
+                d.inode.borrow_mut().meta.modified(now);
+            }
+        }
+        let before = self.children.len();
+        let mut kept = Vec::with_capacity(before);
+        for d in self.children.drain(..) {
+            path.push(d.name);
+            let keep = f(path, &d);
+            path.pop();
+            if keep {
+                kept.push(d);
+            } else {
+                release(d, now);
+            }
+        }
+        self.children = kept;
+        self.children.len() < before
+    }
+}
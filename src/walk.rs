@@ -0,0 +1,160 @@
+//! Lazy traversal of a tree's entries in depth-first or breadth-first order.
+
+use std::collections::VecDeque;
+
+use crate::{DEnt, DTree};
+
+/// The order in which [`Walk`] visits entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Depth-first, each directory before the entries in it.
+    PreOrder,
+    /// Depth-first, each directory after the entries in it.
+    PostOrder,
+    /// Every entry at one depth before any entry at the next.
+    BreadthFirst,
+}
+
+/// An entry visited by [`Walk`]. Cloning it gives another view of the same entry, sharing its
+/// node.
+#[derive(Debug)]
+pub struct WalkEntry<'a> {
+    /// The component names leading to the entry from the top of the walk, ending with its
+    /// own name.
+    pub path: Vec<&'a str>,
+    /// The number of components in `path`: 1 for entries at the top of the walk.
+    pub depth: usize,
+    /// The entry itself.
+    pub entry: DEnt<'a>,
+}
+
+impl<'a> Clone for WalkEntry<'a> {
+    fn clone(&self) -> Self {
+        WalkEntry {
+            path: self.path.clone(),
+            depth: self.depth,
+            entry: self.entry.share(),
+        }
+    }
+}
+
+/// An entry waiting to be visited.
+#[derive(Debug)]
+struct Pending<'a> {
+    path: Vec<&'a str>,
+    entry: DEnt<'a>,
+    /// True once the entries in a directory have been queued, for post-order.
+    expanded: bool,
+}
+
+/// A lazy iterator over the entries of a [`DTree`], as returned by [`DTree::walk`]. By default
+/// it visits leaves in pre-order at every depth: files, symbolic links and empty directories.
+/// Symbolic links are not followed; a directory with several names is visited under each.
+#[derive(Debug)]
+pub struct Walk<'t, 'a> {
+    /// The tree to start from, until the first entry is asked for.
+    root: Option<&'t DTree<'a>>,
+    pending: VecDeque<Pending<'a>>,
+    order: Order,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    include_dirs: bool,
+}
+
+impl<'t, 'a> Walk<'t, 'a> {
+    /// Start a walk over the entries below `root`.
+    pub(crate) fn new(root: &'t DTree<'a>) -> Self {
+        Walk {
+            root: Some(root),
+            pending: VecDeque::new(),
+            order: Order::PreOrder,
+            min_depth: 1,
+            max_depth: None,
+            include_dirs: false,
+        }
+    }
+
+    /// Visit entries in the given `order`.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Only yield entries at `depth` or deeper.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Do not go below `depth`. Directories at `depth` are not leaves unless they are empty.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Yield directories that have entries as well as leaves, if `include` is set.
+    pub fn include_dirs(mut self, include: bool) -> Self {
+        self.include_dirs = include;
+        self
+    }
+
+    /// Queue the entries of `dt`, which is at `path`, to be visited next in this walk's
+    /// order.
+    fn queue(&mut self, dt: &DTree<'a>, path: &[&'a str]) {
+        let pending = dt.children.iter().map(|d| {
+            let mut path = path.to_vec();
+            path.push(d.name);
+            Pending {
+                path,
+                entry: d.share(),
+                expanded: false,
+            }
+        });
+        match self.order {
+            Order::BreadthFirst => self.pending.extend(pending),
+            Order::PreOrder | Order::PostOrder => {
+                let pending: Vec<_> = pending.collect();
+                self.pending.extend(pending.into_iter().rev());
+            }
+        }
+    }
+}
+
+impl<'t, 'a> Iterator for Walk<'t, 'a> {
+    type Item = WalkEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.queue(root, &[]);
+        }
+        loop {
+            let mut p = match self.order {
+                Order::BreadthFirst => self.pending.pop_front()?,
+                Order::PreOrder | Order::PostOrder => self.pending.pop_back()?,
+            };
+            let depth = p.path.len();
+            let descend = self.max_depth.is_none_or(|max| depth < max);
+            let subdir = p.entry.subdir().map(|dt| dt.children.is_empty());
+            if descend && subdir == Some(false) && !p.expanded {
+                let entry = p.entry.share();
+                let dt = entry.subdir().expect("entry is a directory");
+                if self.order == Order::PostOrder {
+                    p.expanded = true;
+                    let path = p.path.clone();
+                    self.pending.push_back(p);
+                    self.queue(&dt, &path);
+                    continue;
+                }
+                self.queue(&dt, &p.path);
+            }
+            let leaf = subdir != Some(false);
+            if depth >= self.min_depth && (leaf || self.include_dirs) {
+                return Some(WalkEntry {
+                    path: p.path,
+                    depth,
+                    entry: p.entry,
+                });
+            }
+        }
+    }
+}